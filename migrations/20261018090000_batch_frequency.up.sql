@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- `stream` now computes term_count and frequency once per document,
+-- so the per-row trigger is no longer needed.
+DROP TRIGGER IF EXISTS update_doc_info;
@@ -0,0 +1,7 @@
+-- Add up migration script here
+CREATE TABLE doc_errors (
+    document text not null primary key,
+    path text not null,
+    stage text not null, -- read, utf8, markdown, tokenize or database
+    message text not null
+);
@@ -0,0 +1,3 @@
+-- Add up migration script here
+-- Documents ingested before this migration have no recorded status; they are assumed complete.
+ALTER TABLE doc_info ADD COLUMN status text not null default 'complete' check (status IN ('pending', 'complete'));
@@ -0,0 +1,3 @@
+-- Add down migration script here
+ALTER TABLE doc_info DROP COLUMN mtime;
+ALTER TABLE doc_info DROP COLUMN content_hash;
@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Documents ingested before this migration have no fingerprint and are re-ingested once by `stream`.
+ALTER TABLE doc_info ADD COLUMN content_hash text; -- hex SHA-256 of the file contents
+ALTER TABLE doc_info ADD COLUMN mtime integer; -- nanoseconds since the Unix epoch
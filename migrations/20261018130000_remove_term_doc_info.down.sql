@@ -0,0 +1,2 @@
+-- Add down migration script here
+DROP TRIGGER remove_term_doc_info;
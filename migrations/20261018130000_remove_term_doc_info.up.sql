@@ -0,0 +1,7 @@
+-- Add up migration script here
+-- Mirrors `update_term_doc_info` so removing a document's rows keeps document counts in step.
+CREATE TRIGGER IF NOT EXISTS remove_term_doc_info AFTER DELETE ON term_info
+BEGIN
+    UPDATE term_doc_info SET doc_count = doc_count - 1 WHERE term = old.lower;
+    DELETE FROM term_doc_info WHERE term = old.lower AND doc_count <= 0;
+END;
@@ -0,0 +1,15 @@
+-- Add down migration script here
+DROP TRIGGER update_term_doc_info;
+DROP TRIGGER remove_term_doc_info;
+
+CREATE TRIGGER update_term_doc_info AFTER INSERT ON term_info
+BEGIN
+    INSERT INTO term_doc_info (term, doc_count) VALUES (new.lower, 1)
+    ON CONFLICT (term) DO UPDATE SET doc_count = doc_count + 1;
+END;
+
+CREATE TRIGGER remove_term_doc_info AFTER DELETE ON term_info
+BEGIN
+    UPDATE term_doc_info SET doc_count = doc_count - 1 WHERE term = old.lower;
+    DELETE FROM term_doc_info WHERE term = old.lower AND doc_count <= 0;
+END;
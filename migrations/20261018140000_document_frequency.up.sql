@@ -0,0 +1,29 @@
+-- Add up migration script here
+-- term_doc_info.doc_count is the number of distinct documents containing at least one
+-- surface form whose lowercase is `term`. Case variants within a document count once.
+DROP TRIGGER IF EXISTS update_term_doc_info;
+DROP TRIGGER IF EXISTS remove_term_doc_info;
+
+CREATE TRIGGER update_term_doc_info AFTER INSERT ON term_info
+WHEN NOT EXISTS (
+    SELECT 1 FROM term_info
+    WHERE document = new.document AND lower = new.lower AND term != new.term
+)
+BEGIN
+    INSERT INTO term_doc_info (term, doc_count) VALUES (new.lower, 1)
+    ON CONFLICT (term) DO UPDATE SET doc_count = doc_count + 1;
+END;
+
+CREATE TRIGGER remove_term_doc_info AFTER DELETE ON term_info
+WHEN NOT EXISTS (
+    SELECT 1 FROM term_info
+    WHERE document = old.document AND lower = old.lower
+)
+BEGIN
+    UPDATE term_doc_info SET doc_count = doc_count - 1 WHERE term = old.lower;
+    DELETE FROM term_doc_info WHERE term = old.lower AND doc_count <= 0;
+END;
+
+DELETE FROM term_doc_info;
+INSERT INTO term_doc_info (term, doc_count)
+SELECT lower, COUNT(DISTINCT document) FROM term_info GROUP BY lower;
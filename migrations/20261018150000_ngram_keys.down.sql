@@ -0,0 +1,23 @@
+-- Add down migration script here
+CREATE TABLE ngram_two_unkeyed (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    occurence integer not null
+);
+INSERT INTO ngram_two_unkeyed SELECT document, term, lower1, lower2, occurence FROM ngram_two;
+DROP TABLE ngram_two;
+ALTER TABLE ngram_two_unkeyed RENAME TO ngram_two;
+
+CREATE TABLE ngram_three_unkeyed (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    lower3 text not null,
+    occurence integer not null
+);
+INSERT INTO ngram_three_unkeyed SELECT document, term, lower1, lower2, lower3, occurence FROM ngram_three;
+DROP TABLE ngram_three;
+ALTER TABLE ngram_three_unkeyed RENAME TO ngram_three;
@@ -0,0 +1,49 @@
+-- Add up migration script here
+-- One row per document and lowercase n-gram, like term_info. `term` keeps one surface form;
+-- `frequency` is the occurrence over all n-grams of the same length in the document.
+CREATE TABLE ngram_two_keyed (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    occurence integer not null,
+    frequency real,
+    primary key (document, lower1, lower2)
+);
+
+INSERT INTO ngram_two_keyed (document, term, lower1, lower2, occurence)
+SELECT document, MIN(term), lower1, lower2, SUM(occurence)
+FROM ngram_two
+GROUP BY document, lower1, lower2;
+
+DROP TABLE ngram_two;
+ALTER TABLE ngram_two_keyed RENAME TO ngram_two;
+
+UPDATE ngram_two
+SET frequency = cast(occurence as real) / (
+    SELECT SUM(n.occurence) FROM ngram_two n WHERE n.document = ngram_two.document
+);
+
+CREATE TABLE ngram_three_keyed (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    lower3 text not null,
+    occurence integer not null,
+    frequency real,
+    primary key (document, lower1, lower2, lower3)
+);
+
+INSERT INTO ngram_three_keyed (document, term, lower1, lower2, lower3, occurence)
+SELECT document, MIN(term), lower1, lower2, lower3, SUM(occurence)
+FROM ngram_three
+GROUP BY document, lower1, lower2, lower3;
+
+DROP TABLE ngram_three;
+ALTER TABLE ngram_three_keyed RENAME TO ngram_three;
+
+UPDATE ngram_three
+SET frequency = cast(occurence as real) / (
+    SELECT SUM(n.occurence) FROM ngram_three n WHERE n.document = ngram_three.document
+);
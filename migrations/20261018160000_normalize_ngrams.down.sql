@@ -0,0 +1,47 @@
+-- Add down migration script here
+-- N-grams longer than three words are dropped.
+CREATE TABLE ngram_two (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    occurence integer not null,
+    frequency real,
+    primary key (document, lower1, lower2)
+);
+
+INSERT INTO ngram_two (document, term, lower1, lower2, occurence, frequency)
+SELECT ngram_info.document, ngram_info.term, l1.lower, l2.lower, ngram_info.occurence, ngram_info.frequency
+FROM ngram_info
+JOIN ngram ON ngram.id = ngram_info.ngram AND ngram.length = 2
+JOIN ngram_component c1 ON c1.ngram = ngram.id AND c1.position = 0
+JOIN ngram_component c2 ON c2.ngram = ngram.id AND c2.position = 1
+JOIN lexicon l1 ON l1.id = c1.term
+JOIN lexicon l2 ON l2.id = c2.term;
+
+CREATE TABLE ngram_three (
+    document text not null,
+    term text not null,
+    lower1 text not null,
+    lower2 text not null,
+    lower3 text not null,
+    occurence integer not null,
+    frequency real,
+    primary key (document, lower1, lower2, lower3)
+);
+
+INSERT INTO ngram_three (document, term, lower1, lower2, lower3, occurence, frequency)
+SELECT ngram_info.document, ngram_info.term, l1.lower, l2.lower, l3.lower, ngram_info.occurence, ngram_info.frequency
+FROM ngram_info
+JOIN ngram ON ngram.id = ngram_info.ngram AND ngram.length = 3
+JOIN ngram_component c1 ON c1.ngram = ngram.id AND c1.position = 0
+JOIN ngram_component c2 ON c2.ngram = ngram.id AND c2.position = 1
+JOIN ngram_component c3 ON c3.ngram = ngram.id AND c3.position = 2
+JOIN lexicon l1 ON l1.id = c1.term
+JOIN lexicon l2 ON l2.id = c2.term
+JOIN lexicon l3 ON l3.id = c3.term;
+
+DROP TABLE ngram_info;
+DROP TABLE ngram_component;
+DROP TABLE ngram;
+DROP TABLE lexicon;
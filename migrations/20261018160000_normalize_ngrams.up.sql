@@ -0,0 +1,105 @@
+-- Add up migration script here
+-- N-grams of any length share one table. `lexicon` gives every lowercase word an id,
+-- `ngram` identifies a sequence of those ids (`components` is the space-separated ids,
+-- also listed per position in `ngram_component`), and `ngram_info` holds per-document
+-- counts like term_info. `frequency` is the occurrence over all n-grams of the same
+-- length in the document.
+CREATE TABLE lexicon (
+    id integer primary key,
+    lower text not null unique
+);
+
+CREATE TABLE ngram (
+    id integer primary key,
+    length integer not null,
+    components text not null unique
+);
+
+CREATE TABLE ngram_component (
+    ngram integer not null references ngram (id),
+    position integer not null,
+    term integer not null references lexicon (id),
+    primary key (ngram, position)
+);
+
+CREATE INDEX ngram_component_term ON ngram_component (term);
+
+CREATE TABLE ngram_info (
+    document text not null,
+    ngram integer not null references ngram (id),
+    term text not null,
+    occurence integer not null,
+    frequency real,
+    primary key (document, ngram)
+);
+
+CREATE INDEX ngram_info_ngram ON ngram_info (ngram);
+
+INSERT INTO lexicon (lower)
+SELECT lower1 FROM ngram_two
+UNION SELECT lower2 FROM ngram_two
+UNION SELECT lower1 FROM ngram_three
+UNION SELECT lower2 FROM ngram_three
+UNION SELECT lower3 FROM ngram_three;
+
+-- Bigrams
+INSERT INTO ngram (length, components)
+SELECT DISTINCT 2, l1.id || ' ' || l2.id
+FROM ngram_two
+JOIN lexicon l1 ON l1.lower = ngram_two.lower1
+JOIN lexicon l2 ON l2.lower = ngram_two.lower2;
+
+INSERT INTO ngram_component (ngram, position, term)
+SELECT ngram.id, 0, cast(substr(components, 1, instr(components, ' ') - 1) as integer)
+FROM ngram WHERE length = 2
+UNION ALL
+SELECT ngram.id, 1, cast(substr(components, instr(components, ' ') + 1) as integer)
+FROM ngram WHERE length = 2;
+
+INSERT INTO ngram_info (document, ngram, term, occurence, frequency)
+SELECT ngram_two.document, ngram.id, ngram_two.term, ngram_two.occurence, ngram_two.frequency
+FROM ngram_two
+JOIN lexicon l1 ON l1.lower = ngram_two.lower1
+JOIN lexicon l2 ON l2.lower = ngram_two.lower2
+JOIN ngram ON ngram.components = l1.id || ' ' || l2.id;
+
+-- Trigrams
+INSERT INTO ngram (length, components)
+SELECT DISTINCT 3, l1.id || ' ' || l2.id || ' ' || l3.id
+FROM ngram_three
+JOIN lexicon l1 ON l1.lower = ngram_three.lower1
+JOIN lexicon l2 ON l2.lower = ngram_three.lower2
+JOIN lexicon l3 ON l3.lower = ngram_three.lower3;
+
+INSERT INTO ngram_component (ngram, position, term)
+SELECT DISTINCT ngram.id, 0, l1.id
+FROM ngram_three
+JOIN lexicon l1 ON l1.lower = ngram_three.lower1
+JOIN lexicon l2 ON l2.lower = ngram_three.lower2
+JOIN lexicon l3 ON l3.lower = ngram_three.lower3
+JOIN ngram ON ngram.components = l1.id || ' ' || l2.id || ' ' || l3.id
+UNION
+SELECT DISTINCT ngram.id, 1, l2.id
+FROM ngram_three
+JOIN lexicon l1 ON l1.lower = ngram_three.lower1
+JOIN lexicon l2 ON l2.lower = ngram_three.lower2
+JOIN lexicon l3 ON l3.lower = ngram_three.lower3
+JOIN ngram ON ngram.components = l1.id || ' ' || l2.id || ' ' || l3.id
+UNION
+SELECT DISTINCT ngram.id, 2, l3.id
+FROM ngram_three
+JOIN lexicon l1 ON l1.lower = ngram_three.lower1
+JOIN lexicon l2 ON l2.lower = ngram_three.lower2
+JOIN lexicon l3 ON l3.lower = ngram_three.lower3
+JOIN ngram ON ngram.components = l1.id || ' ' || l2.id || ' ' || l3.id;
+
+INSERT INTO ngram_info (document, ngram, term, occurence, frequency)
+SELECT ngram_three.document, ngram.id, ngram_three.term, ngram_three.occurence, ngram_three.frequency
+FROM ngram_three
+JOIN lexicon l1 ON l1.lower = ngram_three.lower1
+JOIN lexicon l2 ON l2.lower = ngram_three.lower2
+JOIN lexicon l3 ON l3.lower = ngram_three.lower3
+JOIN ngram ON ngram.components = l1.id || ' ' || l2.id || ' ' || l3.id;
+
+DROP TABLE ngram_two;
+DROP TABLE ngram_three;
@@ -0,0 +1,3 @@
+-- Add down migration script here
+DROP INDEX IF EXISTS term_info_lemma;
+ALTER TABLE term_info DROP COLUMN lemma;
@@ -0,0 +1,5 @@
+-- Add up migration script here
+-- Root of the lowercase form, filled in at ingest time. Rows ingested before this
+-- migration have no lemma and fall back to `lower` until their document is re-ingested.
+ALTER TABLE term_info ADD COLUMN lemma TEXT;
+CREATE INDEX term_info_lemma ON term_info (lemma);
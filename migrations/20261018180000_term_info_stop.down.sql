@@ -0,0 +1,2 @@
+-- Add down migration script here
+ALTER TABLE term_info DROP COLUMN is_stop;
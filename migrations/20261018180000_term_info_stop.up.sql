@@ -0,0 +1,4 @@
+-- Add up migration script here
+-- Whether the surface form was marked as a stopword at ingest time. Stopwords are kept
+-- for document length but left out of rankings; older rows are treated as content words.
+ALTER TABLE term_info ADD COLUMN is_stop BOOLEAN NOT NULL DEFAULT FALSE;
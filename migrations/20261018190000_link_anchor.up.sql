@@ -0,0 +1,14 @@
+-- Add up migration script here
+-- Link text per document. `lower` is the space-joined key of the anchor's words, so a
+-- one-word anchor matches term_info.lower and a longer one the words of an n-gram.
+-- `target` is the link URL, or the label of a reference link without a definition.
+CREATE TABLE link_anchor (
+    document text not null,
+    lower text not null,
+    target text not null,
+    term text not null,
+    occurence integer not null,
+    primary key (document, lower, target)
+);
+
+CREATE INDEX link_anchor_lower ON link_anchor (lower);
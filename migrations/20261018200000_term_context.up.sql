@@ -0,0 +1,10 @@
+-- Add up migration script here
+-- Occurrences of a surface form outside body text, per structural context
+-- (heading, emphasis, table or list). Body occurrences are the rest of term_info.occurence.
+CREATE TABLE term_context (
+    document text not null,
+    term text not null,
+    context text not null CHECK (context IN ('heading', 'emphasis', 'table', 'list')),
+    occurence integer not null,
+    primary key (document, term, context)
+);
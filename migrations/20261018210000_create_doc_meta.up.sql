@@ -0,0 +1,15 @@
+-- Add up migration script here
+-- Front matter fields of each document, flattened into dotted keys such as
+-- `revision.0.timestamp`. `number` is the value read as a number, when it is one,
+-- so that fields can be compared numerically. Documents whose front matter cannot be
+-- parsed are recorded in doc_errors with the stage `metadata`.
+CREATE TABLE doc_meta (
+    document text not null,
+    key text not null,
+    value text not null,
+    number real,
+    primary key (document, key)
+);
+
+CREATE INDEX doc_meta_key_value ON doc_meta (key, value);
+CREATE INDEX doc_meta_key_number ON doc_meta (key, number);
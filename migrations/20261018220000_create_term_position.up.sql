@@ -0,0 +1,18 @@
+-- Add up migration script here
+-- Optional positional index, written by `stream --positions`: every occurrence of a
+-- surface form in a document as a blob of LEB128 varints, four per occurrence in
+-- order of offset (see src/positions.rs):
+--   token offset among the running words, as a difference from the previous one;
+--   byte start in the text extracted from the markdown, as a difference;
+--   byte length;
+--   sentence id, as a difference.
+-- `lower` is the key of the surface form, as in term_info.
+CREATE TABLE term_position (
+    document text not null,
+    term text not null,
+    lower text not null,
+    positions blob not null,
+    primary key (document, term)
+);
+
+CREATE INDEX term_position_lower ON term_position (lower);
@@ -1,21 +1,320 @@
-use clap::{Args, Parser};
+use clap::{ArgGroup, Args, Parser, ValueEnum};
+
+#[derive(Args)]
+pub(crate) struct Database {
+    /// Path or `sqlite:` URL of the corpus database; created and migrated if needed
+    #[arg(long, env = "CARIGALI_DATABASE")]
+    pub database: String,
+}
 
 #[derive(Args)]
 pub(crate) struct Stream {
     #[arg(long)]
     pub root: std::path::PathBuf,
+    /// Only ingest files whose path relative to `--root` matches one of these globs (e.g. '*.md')
+    #[arg(long)]
+    pub include: Vec<String>,
+    /// Skip files whose path relative to `--root` matches one of these globs
+    #[arg(long)]
+    pub exclude: Vec<String>,
+    /// Descend into symlinked directories and ingest symlinked files
+    #[arg(long)]
+    pub follow_symlinks: bool,
+    /// Maximum number of documents to ingest in this run
+    #[arg(long, required_unless_present = "rescan")]
+    pub size: Option<u64>,
+    /// Longest run of words counted as an n-gram
+    #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u64).range(2..))]
+    pub max_ngram: u64,
+    /// Count words such as `bukunya` whole instead of splitting off their clitics
+    #[arg(long)]
+    pub no_clitic_split: bool,
+    /// Also count words such as `bukunya` whole, besides the `buku` and `nya` they are split into
+    #[arg(long, conflicts_with = "no_clitic_split")]
+    pub keep_clitic_surface: bool,
+    /// Also record where every word occurs, for `concordance`
+    #[arg(long)]
+    pub positions: bool,
+    /// Only ingest files whose front matter matches, in the syntax of `--where` for `mine`;
+    /// documents already ingested are kept
+    #[arg(long = "where", value_name = "CONDITION")]
+    pub conditions: Vec<crate::metadata::Condition>,
+    #[command(flatten)]
+    pub stemming: Stemming,
+    #[command(flatten)]
+    pub jawi: Jawi,
+    #[command(flatten)]
+    pub stopwords: Stopwords,
+    /// Number of documents tokenized in parallel; defaults to the number of logical CPUs
+    #[arg(long)]
+    pub jobs: Option<usize>,
+    /// Stop at the first document that fails instead of recording it in `doc_errors` and moving on
     #[arg(long)]
-    pub size: u64,
+    pub fail_fast: bool,
+    /// Only report which documents were added, changed or removed since they were ingested,
+    /// and which failed to be ingested last time
+    #[arg(long)]
+    pub rescan: bool,
+    #[command(flatten)]
+    pub database: Database,
+}
+
+#[derive(Args)]
+pub(crate) struct Stemming {
+    /// File of Malay root words, one per line, replacing the bundled dictionary of the stemmer
+    #[arg(long)]
+    pub roots: Option<std::path::PathBuf>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum StopwordLanguage {
+    /// Malay
+    Ms,
+    /// Indonesian
+    Id,
+    /// English
+    En,
+}
+
+#[derive(Args)]
+pub(crate) struct Stopwords {
+    /// Bundled stopword lists to use
+    #[arg(long = "stopword-language", value_enum, value_delimiter = ',', default_values_t = [StopwordLanguage::Ms, StopwordLanguage::Id])]
+    pub stopword_languages: Vec<StopwordLanguage>,
+    /// Use none of the bundled stopword lists
+    #[arg(long)]
+    pub no_builtin_stopwords: bool,
+    /// File of further stopwords, one per line
+    #[arg(long)]
+    pub stopwords: Vec<std::path::PathBuf>,
+}
+
+#[derive(Args)]
+pub(crate) struct Jawi {
+    /// Key Jawi words by their Rumi spelling, so that both scripts count as one word
+    #[arg(long)]
+    pub transliterate_jawi: bool,
+    /// File of `jawi<TAB>rumi` spellings added to the bundled transliteration dictionary
+    #[arg(long, requires = "transliterate_jawi")]
+    pub jawi_dictionary: Option<std::path::PathBuf>,
+}
+
+/// Which form of a word occurrences are grouped by.
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum Level {
+    /// As written
+    Surface,
+    Lower,
+    /// Malay root, e.g. `bina` for `pembinaan`
+    Lemma,
+}
+
+impl Level {
+    /// Name bound into queries that pick the column to group by.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Level::Surface => "surface",
+            Level::Lower => "lower",
+            Level::Lemma => "lemma",
+        }
+    }
+}
+
+#[derive(Args)]
+pub(crate) struct SubCorpus {
+    /// Only count documents whose front matter matches, e.g. `ns=0`, `year>=2020`,
+    /// `category=Muzik` or `title^=Sal` (prefix); repeat to require several
+    #[arg(long = "where", value_name = "CONDITION")]
+    pub conditions: Vec<crate::metadata::Condition>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum ScorerKind {
+    TfIdf,
+    SublinearTfIdf,
+    Bm25,
+    LogEntropy,
+    Juilland,
+}
+
+#[derive(Args)]
+pub(crate) struct Scoring {
+    /// How each occurrence of a term in a document is weighed
+    #[arg(long, value_enum, default_value_t = ScorerKind::TfIdf)]
+    pub scorer: ScorerKind,
+    /// BM25 term frequency saturation
+    #[arg(long, default_value_t = 1.2)]
+    pub k1: f64,
+    /// BM25 document length normalisation
+    #[arg(long, default_value_t = 0.75)]
+    pub b: f64,
+    /// How many times an occurrence in a heading counts
+    #[arg(long, default_value_t = 1.0)]
+    pub heading_weight: f64,
+    /// How many times an occurrence in emphasised or bold text counts
+    #[arg(long, default_value_t = 1.0)]
+    pub emphasis_weight: f64,
+    /// How many times an occurrence in a table counts
+    #[arg(long, default_value_t = 1.0)]
+    pub table_weight: f64,
+    /// How many times an occurrence in a list counts
+    #[arg(long, default_value_t = 1.0)]
+    pub list_weight: f64,
 }
 
 #[derive(Args)]
 pub(crate) struct Rank {
     #[arg(long)]
     pub word: String,
+    #[arg(long, value_enum, default_value_t = Level::Lower)]
+    pub level: Level,
+    #[command(flatten)]
+    pub stemming: Stemming,
+    #[command(flatten)]
+    pub jawi: Jawi,
+    #[command(flatten)]
+    pub scoring: Scoring,
+    #[command(flatten)]
+    pub sub_corpus: SubCorpus,
+    #[command(flatten)]
+    pub database: Database,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum MineSort {
+    Max,
+    Mean,
+    Stdev,
+    Df,
+    /// Number of documents in which the term is link text
+    Anchors,
+}
+
+#[derive(Args)]
+pub(crate) struct Mine {
+    /// Number of terms to print
+    #[arg(long, default_value_t = 50)]
+    pub top: usize,
+    /// Statistic of the per-document score to rank terms by
+    #[arg(long, value_enum, default_value_t = MineSort::Max)]
+    pub sort: MineSort,
+    /// Ignore terms that occur in fewer documents than this
+    #[arg(long, default_value_t = 1)]
+    pub min_df: i64,
+    #[arg(long, value_enum, default_value_t = Level::Lower)]
+    pub level: Level,
+    #[command(flatten)]
+    pub scoring: Scoring,
+    #[command(flatten)]
+    pub sub_corpus: SubCorpus,
+    #[command(flatten)]
+    pub database: Database,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum KeynessSort {
+    G2,
+    Chi2,
+    PercentDiff,
+    LogRatio,
+}
+
+#[derive(Args)]
+pub(crate) struct Keyness {
+    /// Database of the specialised corpus to find key terms in
+    #[arg(long)]
+    pub target: String,
+    /// Database of the general corpus to compare against
+    #[arg(long)]
+    pub reference: String,
+    /// Number of terms to print
+    #[arg(long, default_value_t = 50)]
+    pub top: usize,
+    #[arg(long, value_enum, default_value_t = KeynessSort::G2)]
+    pub sort: KeynessSort,
+    /// Only keep terms whose log-likelihood is significant at this level
+    #[arg(long)]
+    pub alpha: Option<f64>,
+    /// Divide `--alpha` by the number of terms compared
+    #[arg(long, requires = "alpha")]
+    pub bonferroni: bool,
+    /// Only keep terms at least 2^N times as frequent in the target corpus
+    #[arg(long)]
+    pub min_log_ratio: Option<f64>,
+}
+
+#[derive(ValueEnum, Clone, Copy)]
+pub(crate) enum CollocationSort {
+    Pmi,
+    TScore,
+    Dice,
+    Ll,
+    CValue,
+    NcValue,
+}
+
+#[derive(Args)]
+pub(crate) struct Collocations {
+    /// Number of n-grams to print
+    #[arg(long, default_value_t = 50)]
+    pub top: usize,
+    #[arg(long, value_enum, default_value_t = CollocationSort::Ll)]
+    pub sort: CollocationSort,
+    /// Only print n-grams of this many words
+    #[arg(long, value_parser = clap::value_parser!(u64).range(2..))]
+    pub length: Option<u64>,
+    /// Ignore n-grams occurring fewer times than this across the corpus
+    #[arg(long, default_value_t = 2)]
+    pub min_freq: i64,
+    /// File of words, one per line, that an n-gram may not start or end with
+    #[arg(long)]
+    pub stopwords: Option<std::path::PathBuf>,
+    #[command(flatten)]
+    pub database: Database,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("target").required(true).multiple(true)))]
+pub(crate) struct Remove {
+    /// Document key as stored in `doc_info`, i.e. the path relative to `--root`
+    #[arg(long, group = "target")]
+    pub document: Vec<String>,
+    /// Remove every ingested document whose key matches this glob
+    #[arg(long, group = "target")]
+    pub glob: Vec<String>,
+    #[command(flatten)]
+    pub database: Database,
+}
+
+/// Prints every occurrence of a phrase with the words around it, from documents streamed with `--positions`.
+#[derive(Args)]
+pub(crate) struct Concordance {
+    /// Words to find in this order, split at their clitics like the documents were
+    #[arg(long)]
+    pub phrase: String,
+    /// Number of words shown on each side of the phrase
+    #[arg(long, default_value_t = 5)]
+    pub width: u64,
+    /// Match the phrase word for word, for documents streamed with `--no-clitic-split`
+    #[arg(long)]
+    pub no_clitic_split: bool,
+    #[command(flatten)]
+    pub stemming: Stemming,
+    #[command(flatten)]
+    pub jawi: Jawi,
+    #[command(flatten)]
+    pub sub_corpus: SubCorpus,
+    #[command(flatten)]
+    pub database: Database,
 }
 
 #[derive(Parser)]
 pub(crate) enum Main {
     Stream(Stream),
     Rank(Rank),
+    Remove(Remove),
+    Mine(Mine),
+    Keyness(Keyness),
+    Collocations(Collocations),
+    Concordance(Concordance),
 }
@@ -0,0 +1,279 @@
+use crate::{
+    parser::Token,
+    stemmer::{self, Stemmer},
+};
+
+/// Particles, which attach after any possessive enclitic (`bukunyalah`).
+const PARTICLES: [&str; 4] = ["lah", "kah", "tah", "pun"];
+const POSSESSIVES: [&str; 3] = ["nya", "ku", "mu"];
+const PROCLITICS: [&str; 2] = ["kau", "ku"];
+
+/// Shortest stem left behind when a clitic is detached, so that `buku` or `ilmu` stay whole.
+const MIN_STEM: usize = 3;
+
+/// Lexicalised words that merely end or start like a clitic.
+const EXCEPTIONS: &[&str] = &[
+    // -nya
+    "punya",
+    "hanya",
+    "tanya",
+    "bertanya",
+    "sanya",
+    "bahawasanya",
+    "sesungguhnya",
+    "nyanya",
+    // -lah
+    "sekolah",
+    "masalah",
+    "telah",
+    "salah",
+    "kalah",
+    "olah",
+    "lelah",
+    "allah",
+    "ilah",
+    "kilah",
+    "wilah",
+    "galah",
+    "belah",
+    "sebelah",
+    "pilah",
+    "tulah",
+    "jumlah",
+    "abdullah",
+    "lillah",
+    "daulah",
+    "istilah",
+    // -kah
+    "langkah",
+    "nikah",
+    "kekah",
+    "sedekah",
+    "mekah",
+    "tingkah",
+    // -tah
+    "perintah",
+    "pentah",
+    "latah",
+    "kitah",
+    // -pun
+    "ampun",
+    "himpun",
+    "rumpun",
+    "kapun",
+    "rampun",
+    "tampun",
+    // -ku / -mu
+    "laku",
+    "kuku",
+    "saku",
+    "suku",
+    "paku",
+    "baku",
+    "aku",
+    "tunku",
+    "ilmu",
+    "jamu",
+    "temu",
+    "bertemu",
+    "jemu",
+    "tamu",
+    // ku- / kau-
+    "kuasa",
+    "kuat",
+    "kuda",
+    "kuning",
+    "kucing",
+    "kuliah",
+    "kuala",
+    "kuali",
+    "kubur",
+    "kuih",
+    "kulit",
+    "kumpul",
+    "kunci",
+    "kunjung",
+    "kuntum",
+    "kupas",
+    "kurang",
+    "kursus",
+    "kursi",
+    "kurus",
+    "kutip",
+    "kutub",
+    "kuarters",
+    "kuantan",
+    "kuasai",
+    "kumpulan",
+    "kampung",
+    "kaum",
+    "kaus",
+    "kaunter",
+];
+
+/// Whether `word`, or what is left of it after removing its prefixes, is an exception,
+/// so that `bersekolah` and `melangkah` stay whole like `sekolah` and `langkah`.
+fn is_exception(word: &str) -> bool {
+    fn unprefixed(word: &str, depth: usize) -> bool {
+        EXCEPTIONS.contains(&word)
+            || depth > 0
+                && stemmer::prefix_candidates(word)
+                    .iter()
+                    .any(|stem| unprefixed(stem, depth - 1))
+    }
+    unprefixed(&word.to_lowercase(), stemmer::MAX_PREFIXES)
+}
+
+/// Whether a hyphenated word is a reduplication (kata ganda) such as `buku-buku`.
+fn is_reduplication(word: &str) -> bool {
+    word.split_once('-')
+        .is_some_and(|(left, right)| left.to_lowercase() == right.to_lowercase())
+}
+
+/// Splits `word` before a trailing `clitic`, matching the clitic case-insensitively.
+fn strip_suffix<'a>(word: &'a str, clitic: &str) -> Option<(&'a str, &'a str)> {
+    let at = word.len().checked_sub(clitic.len())?;
+    if !word.is_char_boundary(at) || !word[at..].eq_ignore_ascii_case(clitic) {
+        return None;
+    }
+    let (stem, clitic) = word.split_at(at);
+    (stem.chars().count() >= MIN_STEM).then_some((stem, clitic))
+}
+
+fn strip_prefix<'a>(word: &'a str, clitic: &str) -> Option<(&'a str, &'a str)> {
+    if !word.is_char_boundary(clitic.len()) || !word[..clitic.len()].eq_ignore_ascii_case(clitic) {
+        return None;
+    }
+    let (clitic, stem) = word.split_at(clitic.len());
+    (stem.chars().count() >= MIN_STEM).then_some((clitic, stem))
+}
+
+/// Detaches the clitics of one word, in the order they appear in it. Clitics only come off
+/// a stem whose root is in the dictionary of `stemmer`, so that `Tengku`, `bantah` or `Kuantan`
+/// stay whole; a hyphenated word only loses enclitics that leave a reduplication, as
+/// `buku-bukunya` does but `buku-buku` would not.
+pub(crate) fn split_word<'a>(word: &'a str, stemmer: &Stemmer) -> Vec<&'a str> {
+    let hyphenated = word.contains('-');
+    let proclitic = |stem: &'a str| {
+        PROCLITICS
+            .iter()
+            .find_map(|c| strip_prefix(stem, c))
+            .filter(|(_, rest)| !hyphenated && !is_exception(stem) && stemmer.is_known(rest))
+    };
+    let is_stem = |stem: &'a str| {
+        if hyphenated {
+            is_reduplication(stem)
+        } else {
+            stemmer.is_known(stem)
+                || is_exception(stem)
+                || PROCLITICS.contains(&stem.to_lowercase().as_str())
+                || proclitic(stem).is_some()
+        }
+    };
+
+    // The most enclitics that leave a stem, e.g. both of `bukunyalah` but none of `Tengku`.
+    let mut enclitics = Vec::new();
+    let (mut stem, mut detached) = (word, 0);
+    let mut rest = word;
+    for group in [&PARTICLES[..], &POSSESSIVES[..]] {
+        if is_exception(rest) || hyphenated && is_reduplication(rest) {
+            break;
+        }
+        if let Some((remainder, clitic)) = group.iter().find_map(|c| strip_suffix(rest, c)) {
+            enclitics.push(clitic);
+            rest = remainder;
+            if is_stem(rest) {
+                (stem, detached) = (rest, enclitics.len());
+            }
+        }
+    }
+    enclitics.truncate(detached);
+
+    let mut parts = Vec::new();
+    if let Some((clitic, rest)) = proclitic(stem) {
+        parts.push(clitic);
+        stem = rest;
+    }
+    parts.push(stem);
+    parts.extend(enclitics.into_iter().rev());
+    parts
+}
+
+/// Replaces every word carrying Malay clitics with its stem and clitics as separate tokens.
+/// With `keep_surface`, the whole word is kept as a `Token::Surface` in front of its parts.
+pub(crate) fn split(tokens: Vec<Token>, keep_surface: bool, stemmer: &Stemmer) -> Vec<Token> {
+    let mut split = Vec::with_capacity(tokens.len());
+    for token in tokens {
+        let Token::Text(word) = &token else {
+            split.push(token);
+            continue;
+        };
+        let parts = split_word(word, stemmer);
+        if parts.len() == 1 {
+            split.push(token);
+            continue;
+        }
+        let parts = parts
+            .into_iter()
+            .map(|p| Token::Text(p.to_string()))
+            .collect::<Vec<_>>();
+        if keep_surface {
+            split.push(Token::Surface(word.clone()));
+        }
+        split.extend(parts);
+    }
+    split
+}
+
+#[cfg(test)]
+#[test]
+fn test_split_word() {
+    let stemmer = Stemmer::load(None).unwrap();
+    let split_word = |word| split_word(word, &stemmer);
+    assert_eq!(split_word("bukunya"), vec!["buku", "nya"]);
+    assert_eq!(split_word("Itulah"), vec!["Itu", "lah"]);
+    assert_eq!(split_word("adakah"), vec!["ada", "kah"]);
+    assert_eq!(split_word("kaulah"), vec!["kau", "lah"]);
+    assert_eq!(split_word("kuambil"), vec!["ku", "ambil"]);
+    assert_eq!(split_word("bukunyalah"), vec!["buku", "nya", "lah"]);
+    assert_eq!(split_word("sekolahnya"), vec!["sekolah", "nya"]);
+    assert_eq!(split_word("buku-bukunya"), vec!["buku-buku", "nya"]);
+    assert_eq!(split_word("pemimpinnya"), vec!["pemimpin", "nya"]);
+    assert_eq!(split_word("rumahmukah"), vec!["rumah", "mu", "kah"]);
+    for word in [
+        "punya",
+        "sekolah",
+        "buku",
+        "ilmu",
+        "kucing",
+        "langkah",
+        "Buku-buku",
+        "kupu-kupu",
+        "berlaku",
+        "bersekolah",
+        "melangkah",
+        "mengaku",
+        "Abdullah",
+        "Tunku",
+        "Kuantan",
+        "Kuching",
+        "kualiti",
+        "kaunseling",
+        "Tengku",
+        "bangku",
+        "bantah",
+        "mentah",
+        "berkah",
+    ] {
+        assert_eq!(split_word(word), vec![word]);
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_clitics_are_stopwords() {
+    let stopwords = crate::stopwords::builtin(crate::cli::StopwordLanguage::Ms);
+    for clitic in PARTICLES.iter().chain(&POSSESSIVES).chain(&PROCLITICS) {
+        assert!(stopwords.contains(*clitic), "{}", clitic);
+    }
+}
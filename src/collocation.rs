@@ -0,0 +1,281 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use sqlx::SqlitePool;
+
+use crate::{cli, db, stopwords};
+
+/// Association scores of one n-gram, over lowercase forms.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Collocation {
+    pub words: Vec<String>,
+    pub frequency: i64,
+    /// Pointwise mutual information, log2 of observed over expected frequency.
+    pub pmi: f64,
+    pub t_score: f64,
+    pub dice: f64,
+    /// Log-likelihood G² of the 2×2 table of the first n-1 words against the last one.
+    pub ll: f64,
+    pub c_value: f64,
+    pub nc_value: f64,
+}
+
+impl Collocation {
+    fn key(&self, sort: cli::CollocationSort) -> f64 {
+        match sort {
+            cli::CollocationSort::Pmi => self.pmi,
+            cli::CollocationSort::TScore => self.t_score,
+            cli::CollocationSort::Dice => self.dice,
+            cli::CollocationSort::Ll => self.ll,
+            cli::CollocationSort::CValue => self.c_value,
+            cli::CollocationSort::NcValue => self.nc_value,
+        }
+    }
+}
+
+/// Aggregated counts of the whole corpus, keyed by lowercase forms.
+#[derive(Debug, Default)]
+pub(crate) struct Counts {
+    /// Running words, `doc_info.term_count` summed so that surface forms kept alongside their
+    /// split clitics are not counted twice.
+    pub size: i64,
+    pub unigrams: HashMap<String, i64>,
+    pub ngrams: HashMap<Vec<String>, i64>,
+}
+
+impl Counts {
+    pub async fn load(pool: &SqlitePool) -> Result<Self, sqlx::Error> {
+        let unigrams = sqlx::query! {
+            r#"SELECT lower, SUM(occurence) as "occurence!: i64" FROM term_info GROUP BY lower"#
+        }
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| (r.lower, r.occurence))
+        .collect();
+        let size = sqlx::query_scalar! {
+            r#"SELECT COALESCE(SUM(term_count), 0) as "size!: i64" FROM doc_info"#
+        }
+        .fetch_one(pool)
+        .await?;
+        let lexicon = sqlx::query! {"SELECT id, lower FROM lexicon"}
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .map(|r| (r.id, r.lower))
+            .collect::<HashMap<_, _>>();
+        let ngrams = sqlx::query! {
+            r#"SELECT ngram.components, SUM(ngram_info.occurence) as "occurence!: i64"
+                FROM ngram_info JOIN ngram ON ngram.id = ngram_info.ngram
+                GROUP BY ngram.id"#
+        }
+        .fetch_all(pool)
+        .await?
+        .into_iter()
+        .map(|r| {
+            let words = r
+                .components
+                .split(' ')
+                .filter_map(|id| lexicon.get(&id.parse::<i64>().ok()?).cloned())
+                .collect_vec();
+            (words, r.occurence)
+        })
+        .collect();
+        Ok(Counts {
+            size,
+            unigrams,
+            ngrams,
+        })
+    }
+
+    fn count(&self, words: &[String]) -> f64 {
+        let count = match words {
+            [word] => self.unigrams.get(word),
+            _ => self.ngrams.get(words),
+        };
+        *count.unwrap_or(&0) as f64
+    }
+}
+
+/// G² of a 2×2 contingency table given the joint count, both marginals and the sample size.
+fn log_likelihood(joint: f64, first: f64, second: f64, n: f64) -> f64 {
+    let observed = [
+        joint,
+        first - joint,
+        second - joint,
+        n - first - second + joint,
+    ];
+    let expected = [
+        first * second / n,
+        first * (n - second) / n,
+        (n - first) * second / n,
+        (n - first) * (n - second) / n,
+    ];
+    2. * observed
+        .iter()
+        .zip(expected)
+        .filter(|(o, e)| **o > 0. && e > &0.)
+        .map(|(o, e)| o * (o / e).ln())
+        .sum::<f64>()
+}
+
+/// Scores every n-gram with at least `min_freq` occurrences that neither starts nor ends with a stopword.
+///
+/// C-value treats the longer candidates an n-gram occurs in as its nesting terms. NC-value takes
+/// the context words of an n-gram to be the words extending it by one into another candidate,
+/// weighted by the share of candidates each context word extends.
+pub(crate) fn score(
+    counts: &Counts,
+    min_freq: i64,
+    stopwords: &HashSet<String>,
+) -> Vec<Collocation> {
+    let n = counts.size as f64;
+    let candidates = counts
+        .ngrams
+        .iter()
+        .filter(|(_, f)| **f >= min_freq)
+        .filter(|(words, _)| match (words.first(), words.last()) {
+            (Some(first), Some(last)) => !stopwords.contains(first) && !stopwords.contains(last),
+            _ => false,
+        })
+        .map(|(words, f)| (words.as_slice(), *f as f64))
+        .collect::<HashMap<_, _>>();
+
+    // Frequencies of the candidates each candidate is nested in, and the words extending it by one.
+    let mut nested: HashMap<&[String], Vec<f64>> = HashMap::new();
+    let mut contexts: HashMap<&[String], Vec<(&str, f64)>> = HashMap::new();
+    for (words, f) in &candidates {
+        let inner = (2..words.len())
+            .flat_map(|len| words.windows(len))
+            .filter(|inner| candidates.contains_key(inner))
+            .unique();
+        for inner in inner {
+            nested.entry(inner).or_default().push(*f);
+        }
+        let len = words.len();
+        if len > 2 {
+            if candidates.contains_key(&words[..len - 1]) {
+                contexts
+                    .entry(&words[..len - 1])
+                    .or_default()
+                    .push((&words[len - 1], *f));
+            }
+            if candidates.contains_key(&words[1..]) {
+                contexts
+                    .entry(&words[1..])
+                    .or_default()
+                    .push((&words[0], *f));
+            }
+        }
+    }
+    let mut context_weight: HashMap<&str, f64> = HashMap::new();
+    for words in contexts.values() {
+        for word in words.iter().map(|(w, _)| *w).unique() {
+            *context_weight.entry(word).or_default() += 1.;
+        }
+    }
+    for weight in context_weight.values_mut() {
+        *weight /= candidates.len().max(1) as f64;
+    }
+
+    candidates
+        .iter()
+        .map(|(words, f)| {
+            let k = words.len() as f64;
+            let marginals = words.iter().map(|w| counts.count(std::slice::from_ref(w)));
+            let expected = marginals.clone().product::<f64>() / n.powf(k - 1.);
+            let c_value = k.log2()
+                * match nested.get(words) {
+                    Some(longer) => f - longer.iter().sum::<f64>() / longer.len() as f64,
+                    None => *f,
+                };
+            let context = contexts
+                .get(words)
+                .map(|c| {
+                    c.iter()
+                        .map(|(w, f)| f * context_weight.get(w).unwrap_or(&0.))
+                        .sum::<f64>()
+                })
+                .unwrap_or(0.);
+            Collocation {
+                words: words.to_vec(),
+                frequency: *f as i64,
+                pmi: (f / expected).log2(),
+                t_score: (f - expected) / f.sqrt(),
+                dice: k * f / marginals.sum::<f64>(),
+                ll: log_likelihood(
+                    *f,
+                    counts.count(&words[..words.len() - 1]),
+                    counts.count(&words[words.len() - 1..]),
+                    n,
+                ),
+                c_value,
+                nc_value: 0.8 * c_value + 0.2 * context,
+            }
+        })
+        .collect()
+}
+
+pub(crate) async fn collocations(arg: cli::Collocations) {
+    let pool = db::connect(&arg.database).await.unwrap();
+    let stopwords = match &arg.stopwords {
+        Some(path) => stopwords::load(path).unwrap(),
+        None => HashSet::new(),
+    };
+    let counts = Counts::load(&pool).await.unwrap();
+
+    println!("ngram\tn\tfrequency\tpmi\tt_score\tdice\tll\tc_value\tnc_value");
+    for c in score(&counts, arg.min_freq, &stopwords)
+        .into_iter()
+        .filter(|c| arg.length.is_none_or(|n| c.words.len() as u64 == n))
+        .sorted_by(|a, b| {
+            b.key(arg.sort)
+                .total_cmp(&a.key(arg.sort))
+                .then_with(|| a.words.cmp(&b.words))
+        })
+        .take(arg.top)
+    {
+        println!(
+            "{}\t{}\t{}\t{:.3}\t{:.3}\t{:.4}\t{:.3}\t{:.3}\t{:.3}",
+            c.words.join(" "),
+            c.words.len(),
+            c.frequency,
+            c.pmi,
+            c.t_score,
+            c.dice,
+            c.ll,
+            c.c_value,
+            c.nc_value
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_c_value() {
+    let words = |w: &str| w.split(' ').map(String::from).collect_vec();
+    let mut counts = Counts::default();
+    for (w, f) in [("alat", 10), ("muzik", 8), ("tiup", 6), ("yang", 50)] {
+        counts.unigrams.insert(w.into(), f);
+        counts.size += f;
+    }
+    counts.ngrams.insert(words("alat muzik"), 8);
+    counts.ngrams.insert(words("muzik tiup"), 5);
+    counts.ngrams.insert(words("alat yang"), 2);
+    counts.ngrams.insert(words("alat muzik tiup"), 4);
+
+    let stopwords = HashSet::from(["yang".to_string()]);
+    let scored = score(&counts, 2, &stopwords);
+    assert!(scored.iter().all(|c| c.words != words("alat yang")));
+    let bigram = scored
+        .iter()
+        .find(|c| c.words == words("alat muzik"))
+        .unwrap();
+    assert_eq!(bigram.c_value, 8. - 4.);
+    assert!(bigram.nc_value > 0.8 * bigram.c_value);
+    let trigram = scored
+        .iter()
+        .find(|c| c.words == words("alat muzik tiup"))
+        .unwrap();
+    assert_eq!(trigram.c_value, 3f64.log2() * 4.);
+}
@@ -0,0 +1,150 @@
+use std::path::{Path, PathBuf};
+
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use walkdir::WalkDir;
+
+use crate::cli;
+
+/// Decides which files under `--root` belong to the corpus.
+pub(crate) struct Filter {
+    include: Option<GlobSet>,
+    exclude: GlobSet,
+}
+
+impl Filter {
+    pub fn new(include: &[String], exclude: &[String]) -> Result<Self, globset::Error> {
+        let include = if include.is_empty() {
+            None
+        } else {
+            Some(glob_set(include)?)
+        };
+        Ok(Filter {
+            include,
+            exclude: glob_set(exclude)?,
+        })
+    }
+
+    /// `relative` is the path relative to `--root`.
+    pub fn matches(&self, relative: &str) -> bool {
+        let included = match &self.include {
+            Some(include) => include.is_match(relative),
+            None => true,
+        };
+        included && !self.exclude.is_match(relative)
+    }
+}
+
+pub(crate) fn glob_set(patterns: &[String]) -> Result<GlobSet, globset::Error> {
+    let mut builder = GlobSetBuilder::new();
+    for p in patterns {
+        builder.add(Glob::new(p)?);
+    }
+    builder.build()
+}
+
+/// The key a file is stored under in `doc_info.document`: its path relative to `root`,
+/// always `/`-separated so the same corpus yields the same keys on every platform.
+pub(crate) fn document_key(root: &Path, path: &Path) -> String {
+    path.strip_prefix(root)
+        .unwrap_or(path)
+        .components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Walks `--root` recursively, yielding `(document key, path)` for every file that passes the filter.
+/// Symlinks are only followed with `--follow-symlinks`; loops and unreadable entries are logged and skipped.
+pub(crate) fn discover<'a>(
+    arg: &'a cli::Stream,
+    filter: &'a Filter,
+) -> impl Iterator<Item = (String, PathBuf)> + 'a {
+    WalkDir::new(&arg.root)
+        .follow_links(arg.follow_symlinks)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|entry| match entry {
+            Ok(entry) => Some(entry),
+            Err(err) => {
+                tracing::warn! {target: "carigali", error=%err, "Skipping unreadable entry"};
+                None
+            }
+        })
+        .filter(|entry| entry.file_type().is_file())
+        .map(|entry| (document_key(&arg.root, entry.path()), entry.into_path()))
+        .filter(move |(key, _)| filter.matches(key))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_document_key() {
+        let root = Path::new("/corpus");
+        assert_eq!(document_key(root, Path::new("/corpus/a.md")), "a.md");
+        // Files of the same name in different directories are different documents.
+        assert_eq!(
+            document_key(root, Path::new("/corpus/muzik/saluang/a.md")),
+            "muzik/saluang/a.md"
+        );
+        assert_eq!(
+            document_key(Path::new("/corpus/"), Path::new("/corpus/muzik/a.md")),
+            "muzik/a.md"
+        );
+        assert_eq!(
+            document_key(Path::new("corpus"), Path::new("corpus/muzik/a.md")),
+            "muzik/a.md"
+        );
+    }
+
+    #[test]
+    fn test_filter() {
+        let strings =
+            |patterns: &[&str]| patterns.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+        let everything = Filter::new(&[], &[]).unwrap();
+        assert!(everything.matches("a.md"));
+        assert!(everything.matches("muzik/a.txt"));
+
+        let filter = Filter::new(&strings(&["*.md"]), &strings(&["draf/**"])).unwrap();
+        assert!(filter.matches("a.md"));
+        assert!(filter.matches("muzik/saluang/a.md"));
+        assert!(!filter.matches("a.txt"));
+        // Exclusion wins over inclusion.
+        assert!(!filter.matches("draf/a.md"));
+        assert!(filter.matches("muzik/draf.md"));
+
+        assert!(Filter::new(&strings(&["["]), &[]).is_err());
+    }
+
+    #[test]
+    fn test_discover() {
+        let root = std::env::temp_dir().join(format!("carigali-discover-{}", std::process::id()));
+        for file in ["a.md", "muzik/a.md", "muzik/tiup/a.md", "muzik/nota.txt"] {
+            let path = root.join(file);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, "Saluang").unwrap();
+        }
+        let root_arg = root.to_string_lossy().to_string();
+        let cli::Main::Stream(arg) = <cli::Main as clap::Parser>::parse_from([
+            "carigali",
+            "stream",
+            "--root",
+            &root_arg,
+            "--include",
+            "*.md",
+            "--size",
+            "10",
+            "--database",
+            "sqlite::memory:",
+        ]) else {
+            unreachable!()
+        };
+        let filter = Filter::new(&arg.include, &arg.exclude).unwrap();
+        let keys = discover(&arg, &filter)
+            .map(|(key, _)| key)
+            .collect::<Vec<_>>();
+        std::fs::remove_dir_all(&root).unwrap();
+        assert_eq!(keys, vec!["a.md", "muzik/a.md", "muzik/tiup/a.md"]);
+    }
+}
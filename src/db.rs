@@ -0,0 +1,37 @@
+use std::str::FromStr;
+
+use sqlx::{
+    migrate::Migrator,
+    sqlite::{SqliteConnectOptions, SqlitePool},
+    Error,
+};
+
+use crate::cli;
+
+static MIGRATOR: Migrator = sqlx::migrate!();
+
+/// Opens the corpus database named by `--database`, creating the file if it does not exist
+/// and applying any migration the database is behind on.
+pub(crate) async fn connect(database: &cli::Database) -> Result<SqlitePool, Error> {
+    open(&database.database, true).await
+}
+
+/// Like [`connect`], for commands that read an existing database named by another flag.
+pub(crate) async fn open(url: &str, create: bool) -> Result<SqlitePool, Error> {
+    let options = SqliteConnectOptions::from_str(url)?.create_if_missing(create);
+    let pool = SqlitePool::connect_with(options).await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
+
+/// A migrated in-memory database for tests, on a single connection since every connection
+/// to `:memory:` opens a database of its own.
+#[cfg(test)]
+pub(crate) async fn memory() -> Result<SqlitePool, Error> {
+    let pool = sqlx::sqlite::SqlitePoolOptions::new()
+        .max_connections(1)
+        .connect_with(SqliteConnectOptions::from_str("sqlite::memory:")?)
+        .await?;
+    MIGRATOR.run(&pool).await?;
+    Ok(pool)
+}
@@ -0,0 +1,890 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    time::UNIX_EPOCH,
+};
+
+use itertools::Itertools;
+use sha2::{Digest, Sha256};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool, Transaction};
+
+use crate::{
+    cli, clitic,
+    jawi::Transliterator,
+    metadata::Condition,
+    parser::{self, Context, ParseError, Span, Token},
+    positions::{self, Position},
+    stemmer::Stemmer,
+    stopwords,
+};
+
+/// Rows per multi-row INSERT, kept well under SQLite's bound-parameter limit.
+const ROWS_PER_INSERT: usize = 1000;
+
+/// Punctuation closing a sentence, in Latin and Arabic script.
+const SENTENCE_ENDS: [&str; 4] = [".", "!", "?", "؟"];
+
+/// A row of `doc_info` from a previous run, used to decide whether a file needs (re-)ingesting.
+#[derive(Debug)]
+pub(crate) struct KnownDocument {
+    pub document: String,
+    pub content_hash: Option<String>,
+    pub mtime: Option<i64>,
+    pub status: String,
+}
+
+impl KnownDocument {
+    pub fn is_complete(&self) -> bool {
+        self.status == "complete"
+    }
+}
+
+pub(crate) async fn known_documents(
+    pool: &SqlitePool,
+) -> Result<HashMap<String, KnownDocument>, sqlx::Error> {
+    let rows = sqlx::query_as! {KnownDocument, "SELECT document, content_hash, mtime, status FROM doc_info"}
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().map(|d| (d.document.clone(), d)).collect())
+}
+
+/// Documents whose last ingest failed, as recorded in `doc_errors`.
+pub(crate) async fn failed_documents(pool: &SqlitePool) -> Result<HashSet<String>, sqlx::Error> {
+    let rows = sqlx::query_scalar! {"SELECT document FROM doc_errors"}
+        .fetch_all(pool)
+        .await?;
+    Ok(rows.into_iter().collect())
+}
+
+/// Modification time of `path` in nanoseconds since the Unix epoch, as stored in `doc_info.mtime`.
+pub(crate) fn mtime(path: &Path) -> Option<i64> {
+    let modified = path.metadata().ok()?.modified().ok()?;
+    Some(modified.duration_since(UNIX_EPOCH).ok()?.as_nanos() as i64)
+}
+
+/// What a file looked like when it was ingested.
+#[derive(Debug)]
+pub(crate) struct Fingerprint {
+    pub content_hash: String,
+    pub mtime: Option<i64>,
+}
+
+impl Fingerprint {
+    pub fn of(path: &Path, bytes: &[u8]) -> Self {
+        Fingerprint {
+            content_hash: format!("{:x}", Sha256::digest(bytes)),
+            mtime: mtime(path),
+        }
+    }
+}
+
+/// Outcome of reading a file that is new or whose mtime changed since it was ingested.
+#[derive(Debug)]
+pub(crate) enum Scanned {
+    /// Same content as `known_hash`; only the fingerprint needs refreshing.
+    Unchanged(Fingerprint),
+    /// Front matter not matching every `--where` condition; nothing is written.
+    Filtered,
+    Counted(Fingerprint, Box<DocumentCounts>),
+}
+
+/// How documents are tokenized and counted.
+#[derive(Debug)]
+pub(crate) struct ScanOptions {
+    pub max_ngram: usize,
+    pub split_clitics: bool,
+    pub keep_clitic_surface: bool,
+    pub stemmer: Stemmer,
+    pub jawi: Option<Transliterator>,
+    /// Keys of the words marked as `Token::Stop`.
+    pub stopwords: HashSet<String>,
+    /// Whether to record the positional index in `term_position`.
+    pub positions: bool,
+    /// Front matter conditions a document must meet to be ingested.
+    pub conditions: Vec<Condition>,
+}
+
+impl TryFrom<&cli::Stream> for ScanOptions {
+    type Error = std::io::Error;
+
+    fn try_from(arg: &cli::Stream) -> Result<Self, Self::Error> {
+        Ok(ScanOptions {
+            max_ngram: arg.max_ngram as usize,
+            split_clitics: !arg.no_clitic_split,
+            keep_clitic_surface: arg.keep_clitic_surface,
+            stemmer: Stemmer::load(arg.stemming.roots.as_deref())?,
+            jawi: arg
+                .jawi
+                .transliterate_jawi
+                .then(|| Transliterator::load(arg.jawi.jawi_dictionary.as_deref()))
+                .transpose()?,
+            stopwords: stopwords::from_args(&arg.stopwords)?,
+            positions: arg.positions,
+            conditions: arg.conditions.clone(),
+        })
+    }
+}
+
+impl ScanOptions {
+    /// Splits clitics and marks stopwords.
+    pub fn normalize(&self, tokens: Vec<Token>) -> Vec<Token> {
+        let tokens = if self.split_clitics {
+            clitic::split(tokens, self.keep_clitic_surface, &self.stemmer)
+        } else {
+            tokens
+        };
+        stopwords::mark(tokens, &self.stopwords, |w| self.lower(w))
+    }
+
+    /// [`Self::normalize`], keeping what is known of every token (its context, its byte range)
+    /// for the tokens it becomes.
+    pub fn normalize_spans<S: Clone>(
+        &self,
+        tokens: Vec<Token>,
+        spans: Vec<S>,
+    ) -> (Vec<Token>, Vec<S>) {
+        tokens
+            .into_iter()
+            .zip(spans)
+            .flat_map(|(token, span)| {
+                self.normalize(vec![token])
+                    .into_iter()
+                    .map(move |token| (token, span.clone()))
+            })
+            .unzip()
+    }
+
+    /// Key of a surface form in `term_info.lower` and the lexicon.
+    pub fn lower(&self, term: &str) -> String {
+        self.jawi
+            .as_ref()
+            .and_then(|jawi| jawi.to_rumi(term))
+            .unwrap_or_else(|| term.to_lowercase())
+    }
+}
+
+/// Reads and hashes `path`, tokenizing it only when its content differs from `known_hash`.
+pub(crate) fn scan(
+    path: &Path,
+    known_hash: Option<&str>,
+    options: &ScanOptions,
+) -> Result<Scanned, ParseError> {
+    let bytes = std::fs::read(path).map_err(ParseError::Read)?;
+    let fingerprint = Fingerprint::of(path, &bytes);
+    if known_hash == Some(fingerprint.content_hash.as_str()) {
+        return Ok(Scanned::Unchanged(fingerprint));
+    }
+    let document = parser::tokenize_document(bytes)?;
+    // A malformed front matter leaves the document without metadata rather than unread.
+    let metadata = document.metadata.unwrap_or_else(|err| {
+        tracing::warn! {target: "carigali", path=%path.display(), error=%err, "Ignoring front matter."};
+        Vec::new()
+    });
+    if !options.conditions.iter().all(|c| c.matches(&metadata)) {
+        return Ok(Scanned::Filtered);
+    }
+    let (tokens, spans) = options.normalize_spans(document.tokens, document.spans);
+    let mut counts = DocumentCounts::from_tokens(&tokens, options);
+    counts.count_contexts(&tokens, &spans);
+    if options.positions {
+        counts.count_positions(&tokens, &spans);
+    }
+    counts.metadata = metadata;
+    for anchor in &document.anchors {
+        let tokens = options.normalize(parser::tokenize(&vec![anchor.text.clone()])?);
+        counts.count_anchor(&tokens, &anchor.target, options);
+    }
+    Ok(Scanned::Counted(fingerprint, Box::new(counts)))
+}
+
+/// Term and n-gram occurrences of a single document, counted in memory
+/// so that the document can be written in one transaction.
+#[derive(Debug, Default)]
+pub(crate) struct DocumentCounts {
+    pub terms: HashMap<String, i64>,
+    /// Key of every surface form, see [`ScanOptions::lower`].
+    pub lowers: HashMap<String, String>,
+    /// Root of every key.
+    pub lemmas: HashMap<String, String>,
+    /// Surface forms that are stopwords.
+    pub stops: HashSet<String>,
+    /// Number of running words; surface forms kept alongside their split clitics are not counted twice.
+    pub length: i64,
+    /// Keyed by the keys of their words; the first surface form seen is kept alongside the count.
+    pub ngrams: HashMap<Vec<String>, (String, i64)>,
+    /// Occurrences of surface forms outside body text.
+    pub contexts: HashMap<(String, Context), i64>,
+    /// Flattened front matter fields.
+    pub metadata: Vec<(String, String)>,
+    /// Link anchors keyed by the key of their words and their target, with the first surface form seen.
+    pub anchors: HashMap<(String, String), (String, i64)>,
+    /// Where every surface form occurs, when the positional index is enabled.
+    pub positions: HashMap<String, Vec<Position>>,
+}
+
+impl DocumentCounts {
+    /// Counts n-grams of every length from 2 up to `options.max_ngram`.
+    pub fn from_tokens(tokens: &[Token], options: &ScanOptions) -> Self {
+        let mut counts = DocumentCounts::default();
+        for t in tokens.iter().filter(|t| t.is_term()) {
+            *counts.terms.entry(t.unwrap()).or_default() += 1;
+            if let Token::Stop(word) = t {
+                counts.stops.insert(word.clone());
+            }
+        }
+        for term in counts.terms.keys() {
+            let lower = options.lower(term);
+            counts
+                .lemmas
+                .entry(lower.clone())
+                .or_insert_with_key(|lower| options.stemmer.lemma(lower));
+            counts.lowers.insert(term.clone(), lower);
+        }
+        counts.length = tokens
+            .iter()
+            .filter(|t| matches!(t, Token::Text(_) | Token::Stop(_)))
+            .count() as i64;
+        // Surface forms sit in front of their parts and would otherwise break every window over them.
+        let tokens = tokens
+            .iter()
+            .filter(|t| !matches!(t, Token::Surface(_)))
+            .cloned()
+            .collect::<Vec<_>>();
+        for n in 2..=options.max_ngram {
+            for ng in parser::ngrams(&tokens, n) {
+                let lowers = ng.0.iter().map(|t| counts.lowers[&t.unwrap()].clone());
+                counts
+                    .ngrams
+                    .entry(lowers.collect())
+                    .or_insert_with(|| (ng.term(), 0))
+                    .1 += 1;
+            }
+        }
+        counts
+    }
+
+    /// Counts the terms of `tokens` whose context is not body text.
+    pub fn count_contexts(&mut self, tokens: &[Token], spans: &[Span]) {
+        for (token, span) in tokens.iter().zip(spans) {
+            if token.is_term() && span.context != Context::Body {
+                *self
+                    .contexts
+                    .entry((token.unwrap(), span.context))
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    /// Records the position of every running word of `tokens`. The parts of a word split at its
+    /// clitics each take an offset but share the byte range of the word. A sentence ends at its
+    /// final punctuation or with the block it is in, so that a heading is not read into the
+    /// paragraph below it.
+    pub fn count_positions(&mut self, tokens: &[Token], spans: &[Span]) {
+        let mut offset = 0;
+        let mut sentence = 0;
+        // Block of the last word, unless a sentence already ended after it.
+        let mut block = None;
+        for (token, span) in tokens.iter().zip(spans) {
+            match token {
+                Token::Text(word) | Token::Stop(word) => {
+                    if block.is_some_and(|block| block != span.block) {
+                        sentence += 1;
+                    }
+                    block = Some(span.block);
+                    self.positions
+                        .entry(word.clone())
+                        .or_default()
+                        .push(Position {
+                            offset,
+                            start: span.range.start as u64,
+                            end: span.range.end as u64,
+                            sentence,
+                        });
+                    offset += 1;
+                }
+                other if SENTENCE_ENDS.contains(&other.unwrap().as_str()) => {
+                    sentence += u64::from(block.take().is_some());
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Counts the words of one link anchor pointing to `target`.
+    pub fn count_anchor(&mut self, tokens: &[Token], target: &str, options: &ScanOptions) {
+        let words = tokens
+            .iter()
+            .filter(|t| matches!(t, Token::Text(_) | Token::Stop(_)))
+            .map(Token::unwrap)
+            .collect_vec();
+        if words.is_empty() {
+            return;
+        }
+        let lower = words.iter().map(|w| options.lower(w)).join(" ");
+        self.anchors
+            .entry((lower, target.to_string()))
+            .or_insert_with(|| (words.join(" "), 0))
+            .1 += 1;
+    }
+
+    /// Number of text tokens in the document, stored as `doc_info.term_count`.
+    pub fn term_count(&self) -> i64 {
+        self.length
+    }
+
+    /// Writes the document in a single transaction. `doc_info.status` is `pending` while its rows are
+    /// written and `complete` once they all are, so `stream` never skips a half-counted document.
+    /// Any rows from an earlier version of the document are replaced.
+    pub async fn register(
+        &self,
+        document: &str,
+        fingerprint: &Fingerprint,
+        pool: &SqlitePool,
+    ) -> Result<(), sqlx::Error> {
+        let mut tx = pool.begin().await?;
+        let term_count = self.term_count();
+        sqlx::query! {
+            r#"INSERT INTO doc_info (document, term_count, status, content_hash, mtime) VALUES (?, ?, 'pending', ?, ?)
+                ON CONFLICT (document) DO UPDATE SET
+                    term_count = excluded.term_count,
+                    status = 'pending',
+                    content_hash = excluded.content_hash,
+                    mtime = excluded.mtime"#,
+            document, term_count, fingerprint.content_hash, fingerprint.mtime
+        }
+        .execute(&mut *tx)
+        .await?;
+        sqlx::query! {"DELETE FROM term_info WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query! {"DELETE FROM ngram_info WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query! {"DELETE FROM link_anchor WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query! {"DELETE FROM term_context WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query! {"DELETE FROM doc_meta WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        sqlx::query! {"DELETE FROM term_position WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+
+        let terms = self.terms.iter().collect::<Vec<_>>();
+        for chunk in terms.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO term_info (document, term, lower, lemma, is_stop, occurence) ",
+            );
+            query.push_values(chunk, |mut row, (term, occurence)| {
+                let lower = &self.lowers[*term];
+                row.push_bind(document)
+                    .push_bind(term.as_str())
+                    .push_bind(lower.as_str())
+                    .push_bind(self.lemmas.get(lower).map(String::as_str))
+                    .push_bind(self.stops.contains(*term))
+                    .push_bind(**occurence);
+            });
+            query.push(
+                " ON CONFLICT (document, term, lower) DO UPDATE SET occurence = occurence + excluded.occurence",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+
+        let ngrams = self.ngram_ids(&mut tx).await?;
+        for chunk in ngrams.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO ngram_info (document, ngram, term, occurence) ",
+            );
+            query.push_values(chunk, |mut row, (id, (term, occurence))| {
+                row.push_bind(document)
+                    .push_bind(id)
+                    .push_bind(term.as_str())
+                    .push_bind(occurence);
+            });
+            query.push(
+                " ON CONFLICT (document, ngram) DO UPDATE SET occurence = occurence + excluded.occurence",
+            );
+            query.build().execute(&mut *tx).await?;
+        }
+
+        let contexts = self.contexts.iter().collect::<Vec<_>>();
+        for chunk in contexts.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO term_context (document, term, context, occurence) ",
+            );
+            query.push_values(chunk, |mut row, ((term, context), occurence)| {
+                row.push_bind(document)
+                    .push_bind(term.as_str())
+                    .push_bind(context.as_str())
+                    .push_bind(**occurence);
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        // A key repeated in the front matter keeps its last value.
+        let metadata = self
+            .metadata
+            .iter()
+            .rev()
+            .unique_by(|(key, _)| key)
+            .collect::<Vec<_>>();
+        for chunk in metadata.chunks(ROWS_PER_INSERT) {
+            let mut query =
+                QueryBuilder::<Sqlite>::new("INSERT INTO doc_meta (document, key, value, number) ");
+            query.push_values(chunk, |mut row, (key, value)| {
+                row.push_bind(document)
+                    .push_bind(key.as_str())
+                    .push_bind(value.as_str())
+                    .push_bind(value.parse::<f64>().ok().filter(|n| n.is_finite()));
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        let anchors = self.anchors.iter().collect::<Vec<_>>();
+        for chunk in anchors.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO link_anchor (document, lower, target, term, occurence) ",
+            );
+            query.push_values(chunk, |mut row, ((lower, target), (term, occurence))| {
+                row.push_bind(document)
+                    .push_bind(lower.as_str())
+                    .push_bind(target.as_str())
+                    .push_bind(term.as_str())
+                    .push_bind(*occurence);
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        let positions = self
+            .positions
+            .iter()
+            .map(|(term, positions)| (term, positions::encode(positions)))
+            .collect::<Vec<_>>();
+        for chunk in positions.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "INSERT INTO term_position (document, term, lower, positions) ",
+            );
+            query.push_values(chunk, |mut row, (term, positions)| {
+                row.push_bind(document)
+                    .push_bind(term.as_str())
+                    .push_bind(self.lowers[*term].as_str())
+                    .push_bind(positions.as_slice());
+            });
+            query.build().execute(&mut *tx).await?;
+        }
+
+        sqlx::query! {"DELETE FROM doc_errors WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+
+        // Computed once per document instead of after every inserted row.
+        sqlx::query! {
+            r#"UPDATE term_info
+                SET frequency = cast(occurence as real) / (
+                    SELECT cast(term_count as real) FROM doc_info WHERE doc_info.document = term_info.document
+                )
+                WHERE document = ?"#,
+            document
+        }
+        .execute(&mut *tx)
+        .await?;
+        let totals = self
+            .ngrams
+            .iter()
+            .map(|(lowers, (_, occurence))| (lowers.len() as i64, *occurence))
+            .into_grouping_map()
+            .sum();
+        for (length, total) in totals {
+            let total = total as f64;
+            sqlx::query! {
+                r#"UPDATE ngram_info SET frequency = cast(occurence as real) / ?
+                    WHERE document = ? AND ngram IN (SELECT id FROM ngram WHERE length = ?)"#,
+                total, document, length
+            }
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        sqlx::query! {"UPDATE doc_info SET status = 'complete' WHERE document = ?", document}
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await
+    }
+
+    /// Resolves every counted n-gram to its `ngram.id`, registering new words and n-grams on the way.
+    async fn ngram_ids(
+        &self,
+        tx: &mut Transaction<'_, Sqlite>,
+    ) -> Result<Vec<(i64, &(String, i64))>, sqlx::Error> {
+        let words = self.ngrams.keys().flatten().unique().collect_vec();
+        let mut lexicon = HashMap::new();
+        for chunk in words.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new("INSERT INTO lexicon (lower) ");
+            query.push_values(chunk, |mut row, word| {
+                row.push_bind(word.as_str());
+            });
+            query.push(" ON CONFLICT (lower) DO NOTHING");
+            query.build().execute(&mut **tx).await?;
+
+            let mut query =
+                QueryBuilder::<Sqlite>::new("SELECT id, lower FROM lexicon WHERE lower IN (");
+            let mut values = query.separated(", ");
+            for word in chunk {
+                values.push_bind(word.as_str());
+            }
+            values.push_unseparated(")");
+            for (id, lower) in query
+                .build_query_as::<(i64, String)>()
+                .fetch_all(&mut **tx)
+                .await?
+            {
+                lexicon.insert(lower, id);
+            }
+        }
+
+        let keyed = self
+            .ngrams
+            .iter()
+            .map(|(lowers, counted)| {
+                let ids = lowers.iter().map(|l| lexicon[l]).collect_vec();
+                (ids.iter().join(" "), ids, counted)
+            })
+            .collect_vec();
+        let mut ngrams = HashMap::new();
+        for chunk in keyed.chunks(ROWS_PER_INSERT) {
+            let mut query = QueryBuilder::<Sqlite>::new(
+                "SELECT id, components FROM ngram WHERE components IN (",
+            );
+            let mut values = query.separated(", ");
+            for (components, _, _) in chunk {
+                values.push_bind(components.as_str());
+            }
+            values.push_unseparated(")");
+            for (id, components) in query
+                .build_query_as::<(i64, String)>()
+                .fetch_all(&mut **tx)
+                .await?
+            {
+                ngrams.insert(components, id);
+            }
+
+            let new = chunk
+                .iter()
+                .filter(|(components, _, _)| !ngrams.contains_key(components))
+                .collect_vec();
+            if new.is_empty() {
+                continue;
+            }
+            let mut query = QueryBuilder::<Sqlite>::new("INSERT INTO ngram (length, components) ");
+            query.push_values(&new, |mut row, (components, ids, _)| {
+                row.push_bind(ids.len() as i64)
+                    .push_bind(components.as_str());
+            });
+            query.push(" RETURNING id, components");
+            for (id, components) in query
+                .build_query_as::<(i64, String)>()
+                .fetch_all(&mut **tx)
+                .await?
+            {
+                ngrams.insert(components, id);
+            }
+
+            let components = new
+                .iter()
+                .flat_map(|(components, ids, _)| {
+                    ids.iter()
+                        .enumerate()
+                        .map(|(position, term)| (ngrams[components], position as i64, *term))
+                })
+                .collect_vec();
+            for components in components.chunks(ROWS_PER_INSERT) {
+                let mut query = QueryBuilder::<Sqlite>::new(
+                    "INSERT INTO ngram_component (ngram, position, term) ",
+                );
+                query.push_values(components, |mut row, (ngram, position, term)| {
+                    row.push_bind(ngram).push_bind(position).push_bind(term);
+                });
+                query.build().execute(&mut **tx).await?;
+            }
+        }
+
+        Ok(keyed
+            .into_iter()
+            .map(|(components, _, counted)| (ngrams[&components], counted))
+            .collect())
+    }
+}
+
+/// Refreshes the fingerprint of a document whose mtime changed but whose content did not.
+pub(crate) async fn touch(
+    document: &str,
+    fingerprint: &Fingerprint,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query! {
+        "UPDATE doc_info SET content_hash = ?, mtime = ? WHERE document = ?",
+        fingerprint.content_hash, fingerprint.mtime, document
+    }
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Records why `document` could not be ingested; it is retried on the next `stream`.
+pub(crate) async fn register_error(
+    document: &str,
+    path: &str,
+    stage: &str,
+    message: &str,
+    pool: &SqlitePool,
+) -> Result<(), sqlx::Error> {
+    sqlx::query! {
+        r#"INSERT INTO doc_errors (document, path, stage, message) VALUES (?, ?, ?, ?)
+            ON CONFLICT (document) DO UPDATE SET path = excluded.path, stage = excluded.stage, message = excluded.message"#,
+        document, path, stage, message
+    }
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Deletes every row belonging to `document`; `term_doc_info` follows through its triggers.
+/// Returns whether the document was known at all.
+pub(crate) async fn remove(document: &str, pool: &SqlitePool) -> Result<bool, sqlx::Error> {
+    let mut tx = pool.begin().await?;
+    sqlx::query! {"DELETE FROM term_info WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query! {"DELETE FROM ngram_info WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query! {"DELETE FROM link_anchor WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query! {"DELETE FROM term_context WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query! {"DELETE FROM doc_meta WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    sqlx::query! {"DELETE FROM term_position WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    let errors = sqlx::query! {"DELETE FROM doc_errors WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    let docs = sqlx::query! {"DELETE FROM doc_info WHERE document = ?", document}
+        .execute(&mut *tx)
+        .await?;
+    tx.commit().await?;
+    Ok(docs.rows_affected() + errors.rows_affected() > 0)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn options() -> ScanOptions {
+        ScanOptions {
+            max_ngram: 3,
+            split_clitics: true,
+            keep_clitic_surface: false,
+            stemmer: Stemmer::load(None).unwrap(),
+            jawi: None,
+            stopwords: HashSet::new(),
+            positions: false,
+            conditions: Vec::new(),
+        }
+    }
+
+    fn counts(text: &str) -> DocumentCounts {
+        let options = options();
+        let document = parser::tokenize_document(text.as_bytes().to_vec()).unwrap();
+        DocumentCounts::from_tokens(&options.normalize(document.tokens), &options)
+    }
+
+    fn fingerprint(content_hash: &str) -> Fingerprint {
+        Fingerprint {
+            content_hash: content_hash.to_string(),
+            mtime: None,
+        }
+    }
+
+    async fn doc_count(lower: &str, pool: &SqlitePool) -> i64 {
+        sqlx::query_scalar! {"SELECT doc_count FROM term_doc_info WHERE term = ?", lower}
+            .fetch_optional(pool)
+            .await
+            .unwrap()
+            .unwrap_or(0)
+    }
+
+    async fn terms(document: &str, pool: &SqlitePool) -> Vec<String> {
+        sqlx::query_scalar! {"SELECT term FROM term_info WHERE document = ? ORDER BY term", document}
+            .fetch_all(pool)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_register_and_remove() {
+        let pool = crate::db::memory().await.unwrap();
+
+        counts("Saluang alat muzik. Saluang dan saluang.")
+            .register("a.md", &fingerprint("1"), &pool)
+            .await
+            .unwrap();
+        assert_eq!(doc_count("saluang", &pool).await, 1);
+        let row = sqlx::query! {"SELECT status, term_count, content_hash FROM doc_info WHERE document = 'a.md'"}
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(row.status, "complete");
+        assert_eq!(row.term_count, 6);
+        assert_eq!(row.content_hash.as_deref(), Some("1"));
+
+        counts("Saluang juga.")
+            .register("b.md", &fingerprint("2"), &pool)
+            .await
+            .unwrap();
+        assert_eq!(doc_count("saluang", &pool).await, 2);
+
+        // Changed content replaces every row of the document instead of adding to them.
+        counts("Seruling alat muzik.")
+            .register("a.md", &fingerprint("3"), &pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            terms("a.md", &pool).await,
+            vec!["Seruling", "alat", "muzik"]
+        );
+        assert_eq!(doc_count("saluang", &pool).await, 1);
+        assert_eq!(doc_count("seruling", &pool).await, 1);
+        let ngrams = sqlx::query_scalar! {"SELECT term FROM ngram_info WHERE document = 'a.md' ORDER BY term"}
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(
+            ngrams,
+            vec!["Seruling alat", "Seruling alat muzik", "alat muzik"]
+        );
+
+        assert!(remove("a.md", &pool).await.unwrap());
+        assert_eq!(doc_count("seruling", &pool).await, 0);
+        assert_eq!(doc_count("alat", &pool).await, 0);
+        assert_eq!(doc_count("saluang", &pool).await, 1);
+        assert!(terms("a.md", &pool).await.is_empty());
+        assert!(!remove("a.md", &pool).await.unwrap());
+    }
+
+    #[test]
+    fn test_scan_front_matter() {
+        let dir = std::env::temp_dir().join(format!("carigali-scan-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let broken = dir.join("broken.md");
+        std::fs::write(&broken, "---\ntitle: [broken\n---\nSaluang alat muzik.\n").unwrap();
+        let talk = dir.join("talk.md");
+        std::fs::write(&talk, "---\nns: 1\n---\nBuluh.\n").unwrap();
+
+        let mut options = options();
+        let Ok(Scanned::Counted(_, counts)) = scan(&broken, None, &options) else {
+            panic!("a malformed front matter fails the document");
+        };
+        assert_eq!(counts.length, 3);
+        assert!(counts.metadata.is_empty());
+
+        options.conditions = vec!["ns=0".parse().unwrap()];
+        assert!(matches!(scan(&talk, None, &options), Ok(Scanned::Filtered)));
+        assert!(matches!(
+            scan(&broken, None, &options),
+            Ok(Scanned::Filtered)
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_count_positions() {
+        let text = "# Saluang\n\nAlat muzik. Buluh...\n\n- Tiup\n";
+        let options = options();
+        let document = parser::tokenize_document(text.as_bytes().to_vec()).unwrap();
+        let (tokens, spans) = options.normalize_spans(document.tokens, document.spans);
+        let mut counts = DocumentCounts::default();
+        counts.count_positions(&tokens, &spans);
+        let sentences = ["Saluang", "Alat", "muzik", "Buluh", "Tiup"].map(|word| {
+            let [position] = counts.positions[word][..] else {
+                panic!("{word} occurs once");
+            };
+            assert_eq!(&text[position.start as usize..position.end as usize], word);
+            position.sentence
+        });
+        assert_eq!(sentences, [0, 1, 1, 2, 3]);
+    }
+
+    /// Scans and registers one file the way `stream` does, recording its failure if any.
+    async fn ingest(dir: &Path, document: &str, pool: &SqlitePool) {
+        let path = dir.join(document);
+        let registered = match scan(&path, None, &options()) {
+            Ok(Scanned::Counted(fingerprint, counts)) => counts
+                .register(document, &fingerprint, pool)
+                .await
+                .map_err(|err| ("database", err.to_string())),
+            Ok(_) => Ok(()),
+            Err(err) => Err((err.stage(), err.to_string())),
+        };
+        if let Err((stage, message)) = registered {
+            register_error(document, &path.to_string_lossy(), stage, &message, pool)
+                .await
+                .unwrap();
+        }
+    }
+
+    #[tokio::test]
+    async fn test_register_error() {
+        let pool = crate::db::memory().await.unwrap();
+        let dir = std::env::temp_dir().join(format!("carigali-errors-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("a.md"), "Saluang alat muzik.").unwrap();
+        std::fs::write(dir.join("b.md"), b"Seruling \xff\xfe buluh.").unwrap();
+        std::fs::write(dir.join("c.md"), "Saluang juga.").unwrap();
+
+        for document in ["a.md", "b.md", "c.md"] {
+            ingest(&dir, document, &pool).await;
+        }
+        let errors = sqlx::query! {"SELECT document, stage FROM doc_errors"}
+            .fetch_all(&pool)
+            .await
+            .unwrap();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].document, "b.md");
+        assert_eq!(errors[0].stage, "utf8");
+        assert_eq!(doc_count("saluang", &pool).await, 2);
+        assert!(terms("b.md", &pool).await.is_empty());
+
+        // Once the file is fixed, its next ingest clears the error.
+        std::fs::write(dir.join("b.md"), "Seruling buluh.").unwrap();
+        ingest(&dir, "b.md", &pool).await;
+        let errors = sqlx::query_scalar! {"SELECT COUNT(*) FROM doc_errors"}
+            .fetch_one(&pool)
+            .await
+            .unwrap();
+        assert_eq!(errors, 0);
+        assert_eq!(terms("b.md", &pool).await, vec!["Seruling", "buluh"]);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_register_in_batches() {
+        let pool = crate::db::memory().await.unwrap();
+        let words = (0..ROWS_PER_INSERT * 2 + 1)
+            .map(|i| format!("kata{}", i))
+            .collect::<Vec<_>>();
+        counts(&words.join(" "))
+            .register("a.md", &fingerprint("1"), &pool)
+            .await
+            .unwrap();
+        assert_eq!(terms("a.md", &pool).await.len(), words.len());
+        assert_eq!(doc_count("kata2000", &pool).await, 1);
+    }
+}
@@ -0,0 +1,149 @@
+use std::{collections::HashMap, path::Path};
+
+/// Spellings used when no `--jawi-dictionary` file is given.
+const WORDS: &str = include_str!("../dictionaries/jawi-rumi.tsv");
+
+/// Transliterates Jawi words into Rumi so that both spellings share one `lower` key.
+#[derive(Debug)]
+pub(crate) struct Transliterator {
+    words: HashMap<String, String>,
+}
+
+/// Whether every letter of `word` is in an Arabic-script block.
+pub(crate) fn is_jawi(word: &str) -> bool {
+    let mut letters = word.chars().filter(|c| c.is_alphabetic()).peekable();
+    letters.peek().is_some()
+        && letters.all(|c| {
+            matches!(c as u32, 0x0600..=0x06FF | 0x0750..=0x077F | 0xFB50..=0xFDFF | 0xFE70..=0xFEFF)
+        })
+}
+
+fn parse(dictionary: &str) -> HashMap<String, String> {
+    dictionary
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .filter_map(|l| l.split_once('\t'))
+        .map(|(jawi, rumi)| (jawi.trim().to_string(), rumi.trim().to_lowercase()))
+        .collect()
+}
+
+impl Transliterator {
+    /// Reads `jawi<TAB>rumi` lines from `path` on top of the bundled dictionary.
+    pub fn load(path: Option<&Path>) -> std::io::Result<Self> {
+        let mut words = parse(WORDS);
+        if let Some(path) = path {
+            words.extend(parse(&std::fs::read_to_string(path)?));
+        }
+        Ok(Transliterator { words })
+    }
+
+    /// Rumi spelling of a Jawi `word`, or `None` if it is not written in Jawi.
+    pub fn to_rumi(&self, word: &str) -> Option<String> {
+        if !is_jawi(word) {
+            return None;
+        }
+        let bare = word
+            .chars()
+            .filter(|c| !is_harakat(*c) && *c != '\u{0640}')
+            .collect::<String>();
+        Some(
+            self.words
+                .get(&bare)
+                .cloned()
+                .unwrap_or_else(|| spell(word)),
+        )
+    }
+}
+
+/// Short vowel marks, tanwin, shadda and sukun.
+fn is_harakat(c: char) -> bool {
+    matches!(c, '\u{064B}'..='\u{0652}' | '\u{0670}')
+}
+
+/// Letter-by-letter Rumi spelling. Jawi leaves most vowels unwritten, so unless the word carries
+/// harakat this only approximates the Rumi form (`ڤرکارا` gives `prkara`, not `perkara`).
+fn spell(word: &str) -> String {
+    let chars = word
+        .chars()
+        .filter(|c| *c != '\u{0640}')
+        .collect::<Vec<_>>();
+    let mut rumi = String::new();
+    for (i, c) in chars.iter().enumerate() {
+        let next = chars.get(i + 1);
+        let before_vowel = matches!(next, Some('ا' | 'أ' | 'إ' | 'آ'));
+        let letter = match c {
+            'ا' | 'أ' | 'آ' | 'ى' => {
+                // Initial alif only carries the vowel written by the wau or ya after it.
+                if i == 0 && matches!(next, Some('و' | 'ي' | 'ی')) {
+                    ""
+                } else {
+                    "a"
+                }
+            }
+            'إ' => "i",
+            'ؤ' => "u",
+            'ب' => "b",
+            'ت' | 'ط' | 'ة' => "t",
+            'ث' | 'س' | 'ص' => "s",
+            'ج' => "j",
+            'چ' => "c",
+            'ح' | 'ه' => "h",
+            'خ' => "kh",
+            'د' | 'ض' => "d",
+            'ذ' | 'ز' | 'ظ' => "z",
+            'ر' => "r",
+            'ش' => "sy",
+            'ع' | 'ء' | 'ئ' => "'",
+            'غ' => "gh",
+            'ڠ' => "ng",
+            'ف' => "f",
+            'ڤ' => "p",
+            'ق' | 'ك' | 'ک' => "k",
+            'ݢ' | 'ګ' | 'گ' => "g",
+            'ل' => "l",
+            'م' => "m",
+            'ن' => "n",
+            'ڽ' => "ny",
+            'ۏ' => "v",
+            'و' if i == 0 || before_vowel => "w",
+            'و' => "u",
+            'ي' | 'ی' if i == 0 || before_vowel => "y",
+            'ي' | 'ی' => "i",
+            '\u{064E}' => "a",
+            '\u{0650}' => "i",
+            '\u{064F}' => "u",
+            '\u{064B}' => "an",
+            '\u{064D}' => "in",
+            '\u{064C}' => "un",
+            '\u{0651}' => {
+                // Shadda doubles the consonant before it.
+                let last = rumi.chars().last().filter(|c| c.is_alphabetic());
+                if let Some(last) = last {
+                    rumi.push(last);
+                }
+                ""
+            }
+            c if is_harakat(*c) => "",
+            c => {
+                rumi.push(*c);
+                ""
+            }
+        };
+        rumi.push_str(letter);
+    }
+    rumi
+}
+
+#[cfg(test)]
+#[test]
+fn test_to_rumi() {
+    let jawi = Transliterator::load(None).unwrap();
+    assert_eq!(jawi.to_rumi("ممبينا").as_deref(), Some("membina"));
+    assert_eq!(jawi.to_rumi("ڤرکارا").as_deref(), Some("perkara"));
+    // Harakat do not get in the way of the dictionary.
+    assert_eq!(jawi.to_rumi("مُدرسه").as_deref(), Some("madrasah"));
+    assert_eq!(jawi.to_rumi("ڤوکول").as_deref(), Some("pukul"));
+    assert_eq!(jawi.to_rumi("کَتَبَ").as_deref(), Some("kataba"));
+    assert_eq!(jawi.to_rumi("membina"), None);
+}
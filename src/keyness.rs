@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use sqlx::SqlitePool;
+
+use crate::{cli, db};
+
+/// How much more (or less) often a term occurs in the target corpus than in the reference corpus.
+#[derive(Debug, Clone)]
+pub(crate) struct Keyness {
+    pub target: i64,
+    pub reference: i64,
+    /// Log-likelihood G².
+    pub g2: f64,
+    /// p-value of `g2` under χ² with one degree of freedom.
+    pub p: f64,
+    /// Pearson's χ² of the 2×2 contingency table.
+    pub chi2: f64,
+    /// %DIFF of the normalised frequencies (Gabrielatos & Marchi).
+    pub percent_diff: f64,
+    /// log2 of the ratio of normalised frequencies (Hardie), zero counts adjusted to 0.5.
+    pub log_ratio: f64,
+}
+
+impl Keyness {
+    /// `target` and `reference` are the term's counts, `target_size` and `reference_size` the corpus sizes in tokens.
+    pub fn new(target: i64, reference: i64, target_size: i64, reference_size: i64) -> Self {
+        let (a, b) = (target as f64, reference as f64);
+        let (c, d) = (target_size as f64, reference_size as f64);
+        let n = c + d;
+
+        let e1 = c * (a + b) / n;
+        let e2 = d * (a + b) / n;
+        let ll = |o: f64, e: f64| if o > 0. { o * (o / e).ln() } else { 0. };
+        let g2 = 2. * (ll(a, e1) + ll(b, e2));
+
+        let denominator = (a + b) * (n - a - b) * c * d;
+        let chi2 = if denominator > 0. {
+            n * (a * (d - b) - b * (c - a)).powi(2) / denominator
+        } else {
+            0.
+        };
+
+        let (norm_a, norm_b) = (a / c, b / d);
+        // A zero reference frequency is replaced by a vanishingly small one, as suggested for %DIFF.
+        let percent_diff = (norm_a - norm_b) * 100. / if b > 0. { norm_b } else { 1e-18 };
+        let log_ratio = ((a.max(0.5) / c) / (b.max(0.5) / d)).log2();
+
+        Keyness {
+            target,
+            reference,
+            g2,
+            p: chi2_p_value(g2),
+            chi2,
+            percent_diff,
+            log_ratio,
+        }
+    }
+
+    /// Relatively more frequent in the target than in the reference corpus.
+    pub fn is_overused(&self) -> bool {
+        self.percent_diff > 0.
+    }
+
+    fn key(&self, sort: cli::KeynessSort) -> f64 {
+        match sort {
+            cli::KeynessSort::G2 => self.g2,
+            cli::KeynessSort::Chi2 => self.chi2,
+            cli::KeynessSort::PercentDiff => self.percent_diff,
+            cli::KeynessSort::LogRatio => self.log_ratio,
+        }
+    }
+}
+
+/// Upper tail probability of χ² with one degree of freedom, i.e. erfc(√(x/2)).
+fn chi2_p_value(x: f64) -> f64 {
+    erfc((x.max(0.) / 2.).sqrt())
+}
+
+/// Complementary error function with fractional error below 1.2e-7 (Numerical Recipes' `erfcc`).
+fn erfc(x: f64) -> f64 {
+    let z = x.abs();
+    let t = 1. / (1. + 0.5 * z);
+    let ans = t
+        * (-z * z - 1.26551223
+            + t * (1.00002368
+                + t * (0.37409196
+                    + t * (0.09678418
+                        + t * (-0.18628806
+                            + t * (0.27886807
+                                + t * (-1.13520398
+                                    + t * (1.48851587 + t * (-0.82215223 + t * 0.17087277)))))))))
+            .exp();
+    if x >= 0. {
+        ans
+    } else {
+        2. - ans
+    }
+}
+
+/// Occurrences of every lowercase term in the corpus.
+async fn term_counts(pool: &SqlitePool) -> Result<HashMap<String, i64>, sqlx::Error> {
+    let rows = sqlx::query! {
+        r#"SELECT lower, SUM(occurence) as "occurence!: i64" FROM term_info GROUP BY lower"#
+    }
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.lower, r.occurence)).collect())
+}
+
+/// Running words of the corpus, `doc_info.term_count` summed. Unlike the term counts, surface
+/// forms kept alongside their split clitics are not counted twice.
+async fn corpus_size(pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar! {r#"SELECT COALESCE(SUM(term_count), 0) as "size!: i64" FROM doc_info"#}
+        .fetch_one(pool)
+        .await
+}
+
+/// Lowercase terms marked as stopwords when the corpus was ingested.
+async fn stop_terms(pool: &SqlitePool) -> Result<HashSet<String>, sqlx::Error> {
+    sqlx::query_scalar! {"SELECT DISTINCT lower FROM term_info WHERE is_stop"}
+        .fetch_all(pool)
+        .await
+        .map(|rows| rows.into_iter().collect())
+}
+
+pub(crate) async fn keyness(arg: cli::Keyness) {
+    let target = db::open(&arg.target, false).await.unwrap();
+    let reference = db::open(&arg.reference, false).await.unwrap();
+    let mut stop = stop_terms(&target).await.unwrap();
+    stop.extend(stop_terms(&reference).await.unwrap());
+    let target_size = corpus_size(&target).await.unwrap();
+    let reference_size = corpus_size(&reference).await.unwrap();
+    let target = term_counts(&target).await.unwrap();
+    let reference = term_counts(&reference).await.unwrap();
+
+    // Stopwords still count towards the corpus sizes.
+    let terms = target
+        .keys()
+        .chain(reference.keys())
+        .filter(|term| !stop.contains(*term))
+        .unique()
+        .collect_vec();
+    // With Bonferroni's correction, alpha is shared among every term compared.
+    let alpha = arg.alpha.map(|alpha| {
+        if arg.bonferroni {
+            alpha / terms.len() as f64
+        } else {
+            alpha
+        }
+    });
+
+    let keyness = terms
+        .into_iter()
+        .map(|term| {
+            let k = Keyness::new(
+                *target.get(term).unwrap_or(&0),
+                *reference.get(term).unwrap_or(&0),
+                target_size,
+                reference_size,
+            );
+            (term, k)
+        })
+        .filter(|(_, k)| k.is_overused())
+        .filter(|(_, k)| alpha.is_none_or(|alpha| k.p < alpha))
+        .filter(|(_, k)| arg.min_log_ratio.is_none_or(|min| k.log_ratio >= min))
+        .sorted_by(|(term_a, a), (term_b, b)| {
+            b.key(arg.sort)
+                .total_cmp(&a.key(arg.sort))
+                .then_with(|| term_a.cmp(term_b))
+        });
+
+    println!("term\ttarget\treference\tg2\tp\tchi2\t%diff\tlog_ratio");
+    for (term, k) in keyness.take(arg.top) {
+        println!(
+            "{}\t{}\t{}\t{:.3}\t{:.3e}\t{:.3}\t{:.1}\t{:.3}",
+            term, k.target, k.reference, k.g2, k.p, k.chi2, k.percent_diff, k.log_ratio
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_keyness() {
+    assert!((chi2_p_value(3.841) - 0.05).abs() < 1e-3);
+
+    let even = Keyness::new(10, 100, 1_000, 10_000);
+    assert!(even.g2.abs() < 1e-9);
+    assert!(even.log_ratio.abs() < 1e-9);
+
+    let key = Keyness::new(50, 10, 10_000, 100_000);
+    assert!(key.is_overused());
+    assert!((key.log_ratio - 50f64.log2()).abs() < 1e-9);
+    assert!((key.percent_diff - 4900.).abs() < 1e-6);
+    assert!(key.g2 > 3.841 && key.p < 0.05);
+}
@@ -1,34 +1,42 @@
-use std::ffi::OsString;
+use std::collections::{HashMap, HashSet};
 
 mod cli;
+mod clitic;
+mod collocation;
+mod corpus;
+mod db;
+mod ingest;
+mod jawi;
+mod keyness;
+mod metadata;
+mod mine;
 mod parser;
+mod positions;
+mod scoring;
+mod stemmer;
+mod stopwords;
 
 use clap::Parser;
 use cli::Main;
 use itertools::Itertools;
-use sqlx::SqlitePool;
-use tokio_stream::StreamExt;
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
 
 use tracing::{self, info_span};
 use tracing_indicatif::{span_ext::IndicatifSpanExt, IndicatifLayer};
 use tracing_subscriber::Layer;
 use tracing_subscriber::{self, layer::SubscriberExt, util::SubscriberInitExt};
 
-// TODO
-// - [ ] Since TFIDF is a per document value, how to aggregate tfidf for all documents?
-//          - maybe can use stdev? because uncommon words tend to vary in terms of usage
-
-#[derive(Debug, PartialEq)]
-struct Item {
-    document: Option<String>,
-}
-
 #[tokio::main]
 async fn main() {
     let command = Main::parse();
     match command {
         Main::Stream(streamer) => stream(streamer).await,
         Main::Rank(ranker) => rank(ranker).await,
+        Main::Remove(remover) => remove(remover).await,
+        Main::Mine(miner) => mine::mine(miner).await,
+        Main::Keyness(keyness) => keyness::keyness(keyness).await,
+        Main::Collocations(collocations) => collocation::collocations(collocations).await,
+        Main::Concordance(concordance) => positions::concordance(concordance).await,
     }
 }
 
@@ -39,52 +47,121 @@ struct TermTable {
     lower: String,
     occurence: i64,
     frequency: Option<f64>,
-}
-
-fn rank_term_frequency(items: &Vec<TermTable>) -> Vec<f64> {
-    items
-        .iter()
-        .map(|i| i.frequency.unwrap_or(0.))
-        .collect_vec()
-}
-
-fn rank_inv_document_freuqency(items: &Vec<TermTable>, total_docs: u64) -> f64 {
-    (total_docs as f64 / (1. + items.len() as f64)).log10()
-}
-
-fn rank_tf_idf(items: &Vec<TermTable>, total_docs: u64) -> Vec<f64> {
-    let idf = rank_inv_document_freuqency(items, total_docs);
-    rank_term_frequency(items)
-        .iter()
-        .map(|i| i * idf)
-        .collect_vec()
+    term_count: i64,
 }
 
 async fn rank(arg: cli::Rank) {
-    let pool = SqlitePool::connect(env!("DATABASE_URL"));
-    let pool = pool.await.unwrap();
+    let pool = db::connect(&arg.database).await.unwrap();
 
-    let word_lower = arg.word.to_lowercase();
+    let mut lower = arg.word.to_lowercase();
+    if arg.jawi.transliterate_jawi {
+        let jawi = jawi::Transliterator::load(arg.jawi.jawi_dictionary.as_deref()).unwrap();
+        lower = jawi.to_rumi(&arg.word).unwrap_or(lower);
+    }
+    let key = match arg.level {
+        cli::Level::Surface => arg.word.clone(),
+        cli::Level::Lower => lower,
+        cli::Level::Lemma => stemmer::Stemmer::load(arg.stemming.roots.as_deref())
+            .unwrap()
+            .lemma(&lower),
+    };
+    let level = arg.level.as_str();
 
-    let item = sqlx::query_as! {TermTable, "SELECT * FROM term_info WHERE lower = ?", word_lower}
-        .fetch_all(&pool)
+    // Every form of the word within a document is one occurrence of it at the chosen level.
+    let item = sqlx::query_as! {TermTable,
+        r#"SELECT
+            term_info.document,
+            GROUP_CONCAT(DISTINCT term) as "term!: String",
+            GROUP_CONCAT(DISTINCT lower) as "lower!: String",
+            SUM(occurence) as "occurence!: i64",
+            SUM(frequency) as "frequency: f64",
+            doc_info.term_count
+        FROM term_info
+        JOIN doc_info ON doc_info.document = term_info.document
+        WHERE CASE ?1 WHEN 'surface' THEN term WHEN 'lemma' THEN COALESCE(lemma, lower) ELSE lower END = ?2
+        GROUP BY term_info.document"#,
+        level, key
+    }
+    .fetch_all(&pool)
+    .await
+    .unwrap();
+
+    let documents = metadata::documents(&arg.sub_corpus.conditions, &pool)
         .await
         .unwrap();
-
-    let total_docs = sqlx::query! {"SELECT COUNT(document) as count FROM doc_info"}
-        .fetch_one(&pool)
+    let item = item
+        .into_iter()
+        .filter(|i| documents.as_ref().is_none_or(|d| d.contains(&i.document)))
+        .collect_vec();
+    let corpus = scoring::Corpus::load(&pool, documents.as_ref())
+        .await
+        .unwrap();
+    let extra = scoring::ContextWeights::new(&arg.scoring)
+        .extra(arg.level, Some(&key), &pool)
         .await
         .unwrap();
+    let postings = item
+        .iter()
+        .map(|i| scoring::Posting {
+            occurence: i.occurence as f64
+                + extra.get(&(key.clone(), i.document.clone())).unwrap_or(&0.),
+            length: i.term_count,
+        })
+        .collect_vec();
+    let mut stats = scoring::TermStats::new(&postings, &corpus);
+    // Over the whole corpus, the lowercase form has the document frequency kept at ingestion;
+    // other levels and sub-corpora count the documents their postings come from.
+    if matches!(arg.level, cli::Level::Lower) && documents.is_none() {
+        stats.doc_count = scoring::document_frequency(&key, &pool).await.unwrap();
+    }
+    let scorer = scoring::scorer(&arg.scoring);
 
     println!(
-        "IDF for {} is {:.5}",
-        word_lower,
-        rank_inv_document_freuqency(&item, total_docs.count as u64)
+        "{} occurs in {} of {} documents, IDF is {:.5}",
+        key,
+        stats.doc_count,
+        corpus.documents,
+        stats.idf()
     );
-    let ranks = rank_tf_idf(&item, total_docs.count as u64);
-    for (tfidf, term) in ranks.iter().zip(item).take(10) {
-        println!("TFIDF is {:.5} for {:?}", tfidf, term);
+    let ranks = postings
+        .iter()
+        .map(|p| scorer.score(&stats, p))
+        .zip(item)
+        .sorted_by(|(a, _), (b, _)| b.total_cmp(a));
+    for (score, term) in ranks.take(10) {
+        println!("Score is {:.5} for {:?}", score, term);
+    }
+}
+
+async fn remove(arg: cli::Remove) {
+    let pool = db::connect(&arg.database).await.unwrap();
+
+    let globs = corpus::glob_set(&arg.glob).unwrap();
+    let mut documents = arg.document.clone();
+    if !arg.glob.is_empty() {
+        let known =
+            sqlx::query! {"SELECT document FROM doc_info UNION SELECT document FROM doc_errors"}
+                .fetch_all(&pool)
+                .await
+                .unwrap();
+        documents.extend(
+            known
+                .into_iter()
+                .map(|row| row.document)
+                .filter(|document| globs.is_match(document)),
+        );
     }
+
+    let mut removed = 0;
+    for document in documents.iter().unique() {
+        if ingest::remove(document, &pool).await.unwrap() {
+            println!("Removed {}", document);
+            removed += 1;
+        } else {
+            println!("No such document {}", document);
+        }
+    }
+    println!("Removed {} documents", removed);
 }
 
 async fn stream(arg: cli::Stream) {
@@ -100,12 +177,15 @@ async fn stream(arg: cli::Stream) {
         .with(indicatif_layer)
         .init();
 
-    let pool = SqlitePool::connect(env!("DATABASE_URL"));
-    let pool = pool.await.unwrap();
+    tracing::info! {"--database"=arg.database.database, "Establishing connection:"};
+    let pool = db::connect(&arg.database).await.unwrap();
 
-    let processed =
-        sqlx::query_as! {Item,"SELECT DISTINCT(document) FROM doc_info"}.fetch_all(&pool);
-    tracing::info! {DATABASE_URL=env!("DATABASE_URL"), "Establishing connection:"};
+    let known = ingest::known_documents(&pool).await.unwrap();
+    if arg.rescan {
+        let failed = ingest::failed_documents(&pool).await.unwrap();
+        tokio::task::block_in_place(|| rescan(&arg, &known, &failed));
+        return;
+    }
 
     let pbar_parse_span = info_span!("parser");
     let pbar_skips_span = info_span!("skips");
@@ -117,13 +197,7 @@ async fn stream(arg: cli::Stream) {
         .unwrap()
         .progress_chars("##-"),
     );
-    let processed = processed
-        .await
-        .unwrap_or(vec![])
-        .into_iter()
-        .map(|i| OsString::from(i.document.unwrap_or_default()))
-        .collect::<Vec<OsString>>();
-    let skip_len = processed.len() as u64;
+    let skip_len = known.values().filter(|d| d.is_complete()).count() as u64;
     pbar_skips_span.pb_set_style(
         &indicatif::ProgressStyle::with_template(
             "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len} | {eta:3}",
@@ -132,54 +206,98 @@ async fn stream(arg: cli::Stream) {
         .progress_chars("##-"),
     );
 
-    pbar_parse_span.pb_set_length(arg.size);
+    let size = arg.size.unwrap_or(u64::MAX);
+    pbar_parse_span.pb_set_length(size);
     pbar_skips_span.pb_set_length(skip_len);
 
     let pbar_bytes = indicatif::ProgressBar::hidden();
 
-    let mut count: u64 = 0;
     tracing::info! {"--root"=arg.root.to_str(), "Reading `--root`:"};
-    for i in arg.root.read_dir().unwrap() {
-        if count == arg.size {
+    let filter = corpus::Filter::new(&arg.include, &arg.exclude).unwrap();
+    let mut pending = Vec::new();
+    for (n, path) in corpus::discover(&arg, &filter) {
+        if pending.len() as u64 == size {
             break;
         }
-        let name = i.unwrap();
-        if processed.contains(&name.file_name()) {
-            pbar_skips_span.in_scope(|| {
-                tracing::info! {target: "carigali", filename=&name.file_name().to_str().unwrap(), "Skipping"}
-            });
-            pbar_skips_span.pb_inc(1);
-            continue;
-        }
-        let n = name.file_name().into_string().unwrap();
-        pbar_parse_span.in_scope(|| {
-            tracing::info! {target: "carigali", filename=n, "Reading file."};
-        });
-        let q1 = sqlx::query! {
-            r#"INSERT INTO doc_info (document, term_count) VALUES (?, 0)"#,
-            n
-        }
-        .execute(&pool);
-
-        let tokens = crate::parser::tokenize_file(name.path());
-        let ngram2_tokens = crate::parser::ngram2(&tokens);
-        let ngram3_tokens = crate::parser::ngram3(&tokens);
+        // Only files whose mtime moved are read and hashed; their content decides re-ingestion.
+        let known_hash = match known.get(&n) {
+            Some(doc) if doc.is_complete() => {
+                if doc.mtime.is_some() && doc.mtime == ingest::mtime(&path) {
+                    pbar_skips_span.in_scope(|| {
+                        tracing::info! {target: "carigali", filename=n, "Skipping"}
+                    });
+                    pbar_skips_span.pb_inc(1);
+                    continue;
+                }
+                doc.content_hash.clone()
+            }
+            _ => None,
+        };
+        pending.push((n, path, known_hash));
+    }
 
-        let _ = q1.await.unwrap();
+    // Tokenize on a worker pool and funnel the counts to this task, the only SQLite writer.
+    let workers = rayon::ThreadPoolBuilder::new()
+        // 0 lets rayon use one thread per logical CPU.
+        .num_threads(arg.jobs.unwrap_or(0))
+        .build()
+        .unwrap();
+    let (sender, mut receiver) = tokio::sync::mpsc::channel(workers.current_num_threads() * 2);
+    let parse_span = pbar_parse_span.clone();
+    let options = ingest::ScanOptions::try_from(&arg).unwrap();
+    let tokenizer = tokio::task::spawn_blocking(move || {
+        workers.install(|| {
+            pending
+                .into_par_iter()
+                .for_each_with(sender, |sender, (n, path, known_hash)| {
+                    if sender.is_closed() {
+                        return;
+                    }
+                    parse_span.in_scope(|| {
+                        tracing::info! {target: "carigali", filename=n, "Reading file."};
+                    });
+                    let scanned = ingest::scan(&path, known_hash.as_deref(), &options);
+                    // The writer only stops receiving on `--fail-fast`.
+                    let _ = sender.blocking_send((n, path, scanned));
+                })
+        })
+    });
 
-        let mut stream_tok = tokio_stream::iter(&tokens);
-        while let Some(t) = stream_tok.next().await {
-            if t.is_text() {
-                t.register(&n, &pool).await.unwrap();
-            };
-        }
-        let mut stream_ng2 = tokio_stream::iter(&ngram2_tokens);
-        while let Some(ng2) = stream_ng2.next().await {
-            ng2.register(&n, &pool).await.unwrap();
-        }
-        let mut stream_ng3 = tokio_stream::iter(&ngram3_tokens);
-        while let Some(ng3) = stream_ng3.next().await {
-            ng3.register(&n, &pool).await.unwrap();
+    let mut failures = Vec::new();
+    while let Some((n, path, scanned)) = receiver.recv().await {
+        let registered = match scanned {
+            Ok(ingest::Scanned::Unchanged(fingerprint)) => {
+                ingest::touch(&n, &fingerprint, &pool).await.unwrap();
+                pbar_skips_span.in_scope(|| {
+                    tracing::info! {target: "carigali", filename=n, "Skipping unchanged content"}
+                });
+                pbar_skips_span.pb_inc(1);
+                continue;
+            }
+            Ok(ingest::Scanned::Filtered) => {
+                pbar_skips_span.in_scope(|| {
+                    tracing::info! {target: "carigali", filename=n, "Skipping, front matter does not match `--where`"}
+                });
+                continue;
+            }
+            Ok(ingest::Scanned::Counted(fingerprint, counts)) => counts
+                .register(&n, &fingerprint, &pool)
+                .await
+                .map_err(|err| ("database", err.to_string())),
+            Err(err) => Err((err.stage(), err.to_string())),
+        };
+        if let Err((stage, message)) = registered {
+            pbar_parse_span.in_scope(|| {
+                tracing::warn! {target: "carigali", filename=n, stage, error=message, "Failed to ingest file."};
+            });
+            ingest::register_error(&n, &path.to_string_lossy(), stage, &message, &pool)
+                .await
+                .unwrap();
+            failures.push(stage);
+            if arg.fail_fast {
+                break;
+            }
+            continue;
         }
 
         let n_bytes = n.as_bytes().iter().fold(0, |acc, curr| acc + *curr as u64);
@@ -188,6 +306,77 @@ async fn stream(arg: cli::Stream) {
             tracing::info! {target: "carigali", filename=n, "Done reading file at {:.3} B/s", pbar_bytes.per_sec()};
         });
         pbar_parse_span.pb_inc(1);
-        count += 1
     }
+    drop(receiver);
+    tokenizer.await.unwrap();
+
+    if !failures.is_empty() {
+        for (stage, count) in failures.iter().counts() {
+            tracing::warn! {target: "carigali", stage, count, "Failed documents by stage:"};
+        }
+        tracing::warn! {target: "carigali", "{} documents failed, see the `doc_errors` table", failures.len()};
+        if arg.fail_fast {
+            std::process::exit(1);
+        }
+    }
+}
+
+/// Reports how the files under `--root` differ from what has been ingested, without writing anything.
+/// Files whose last ingest failed are reported as such, since the next `stream` retries them.
+fn rescan(
+    arg: &cli::Stream,
+    known: &HashMap<String, ingest::KnownDocument>,
+    failed: &HashSet<String>,
+) {
+    let filter = corpus::Filter::new(&arg.include, &arg.exclude).unwrap();
+    let found = corpus::discover(arg, &filter).collect_vec();
+    let statuses = found
+        .par_iter()
+        .map(|(n, path)| match known.get(n) {
+            _ if failed.contains(n) => "failed",
+            None => "added",
+            Some(doc)
+                if doc.is_complete() && doc.mtime.is_some() && doc.mtime == ingest::mtime(path) =>
+            {
+                "unchanged"
+            }
+            Some(doc) => {
+                let hash = std::fs::read(path)
+                    .ok()
+                    .map(|bytes| ingest::Fingerprint::of(path, &bytes).content_hash);
+                if doc.is_complete() && hash.is_some() && hash == doc.content_hash {
+                    "unchanged"
+                } else {
+                    "changed"
+                }
+            }
+        })
+        .collect::<Vec<_>>();
+
+    for ((n, _), status) in found.iter().zip(&statuses) {
+        if *status != "unchanged" {
+            println!("{}\t{}", status, n);
+        }
+    }
+    let found = found.iter().map(|(n, _)| n).collect::<HashSet<_>>();
+    let removed = known
+        .keys()
+        .chain(failed)
+        .unique()
+        .filter(|n| filter.matches(n) && !found.contains(n))
+        .sorted()
+        .collect_vec();
+    for n in &removed {
+        println!("removed\t{}", n);
+    }
+
+    let counts = statuses.iter().copied().counts();
+    println!(
+        "{} added, {} changed, {} failed, {} removed, {} unchanged",
+        counts.get("added").unwrap_or(&0),
+        counts.get("changed").unwrap_or(&0),
+        counts.get("failed").unwrap_or(&0),
+        removed.len(),
+        counts.get("unchanged").unwrap_or(&0),
+    );
 }
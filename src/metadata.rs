@@ -0,0 +1,304 @@
+use std::{collections::HashSet, str::FromStr};
+
+use serde_yaml::Value;
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use crate::parser::ParseError;
+
+/// Front matter of a markdown file, as written.
+#[derive(Debug, Clone)]
+pub(crate) enum FrontMatter {
+    Yaml(String),
+    Toml(String),
+}
+
+impl FrontMatter {
+    /// Fields of the front matter flattened into `key = value` pairs, see [`flatten`].
+    pub fn fields(&self) -> Result<Vec<(String, String)>, ParseError> {
+        let value = match self {
+            FrontMatter::Yaml(yaml) => serde_yaml::from_str::<Value>(yaml)
+                .map_err(|err| ParseError::Metadata(err.to_string()))?,
+            FrontMatter::Toml(toml) => toml::from_str::<toml::Value>(toml)
+                .map_err(|err| ParseError::Metadata(err.to_string()))
+                .and_then(|value| {
+                    serde_yaml::to_value(value).map_err(|err| ParseError::Metadata(err.to_string()))
+                })?,
+        };
+        let mut fields = Vec::new();
+        flatten(&value, String::new(), &mut fields);
+        Ok(fields)
+    }
+}
+
+/// Nested mappings are joined with `.` and sequences by index, so the Wikipedia preprocessor's
+/// `revision: [{timestamp: ...}]` becomes `revision.0.timestamp`. Nulls are left out.
+fn flatten(value: &Value, key: String, fields: &mut Vec<(String, String)>) {
+    let child = |name: &str| {
+        if key.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", key, name)
+        }
+    };
+    match value {
+        Value::Null => {}
+        Value::Bool(b) => fields.push((key, b.to_string())),
+        Value::Number(n) => fields.push((key, n.to_string())),
+        Value::String(s) => fields.push((key, s.clone())),
+        Value::Sequence(items) => {
+            for (i, item) in items.iter().enumerate() {
+                flatten(item, child(&i.to_string()), fields);
+            }
+        }
+        Value::Mapping(mapping) => {
+            for (name, item) in mapping {
+                let name = match name {
+                    Value::String(s) => s.clone(),
+                    other => serde_yaml::to_string(other)
+                        .unwrap_or_default()
+                        .trim()
+                        .to_string(),
+                };
+                flatten(item, child(&name), fields);
+            }
+        }
+        Value::Tagged(tagged) => flatten(&tagged.value, key, fields),
+    }
+}
+
+/// Comparison operators of a `--where` condition, two-character ones first.
+/// `^=` keeps the fields whose value starts with the given text.
+const OPERATORS: [&str; 7] = ["!=", ">=", "<=", "^=", "=", ">", "<"];
+
+/// A `--where` condition on a front matter field, such as `ns=0`, `year>=2020` or `title^=Sal`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Condition {
+    pub key: String,
+    pub operator: &'static str,
+    pub value: String,
+}
+
+impl FromStr for Condition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (at, operator) = s
+            .char_indices()
+            .find_map(|(i, _)| {
+                OPERATORS
+                    .iter()
+                    .find(|op| s[i..].starts_with(**op))
+                    .map(|op| (i, *op))
+            })
+            .ok_or_else(|| {
+                format!(
+                    "expected FIELD OP VALUE, OP being one of {}",
+                    OPERATORS.join(" ")
+                )
+            })?;
+        let key = s[..at].trim();
+        if key.is_empty() {
+            return Err("missing field name".to_string());
+        }
+        Ok(Condition {
+            key: key.to_string(),
+            operator,
+            value: s[at + operator.len()..].trim().to_string(),
+        })
+    }
+}
+
+impl Condition {
+    /// Whether flattened `fields` meet the condition, compared like [`documents`] does; the last
+    /// of repeated keys counts and a missing field never matches.
+    pub fn matches(&self, fields: &[(String, String)]) -> bool {
+        let Some((_, value)) = fields.iter().rev().find(|(key, _)| *key == self.key) else {
+            return false;
+        };
+        let ordering = match (self.value.parse::<f64>(), value.parse::<f64>()) {
+            _ if self.operator == "^=" => return value.starts_with(&self.value),
+            (Ok(expected), Ok(actual)) if actual.is_finite() => actual.partial_cmp(&expected),
+            _ => Some(value.as_str().cmp(self.value.as_str())),
+        };
+        let Some(ordering) = ordering else {
+            return false;
+        };
+        match self.operator {
+            "=" => ordering.is_eq(),
+            "!=" => ordering.is_ne(),
+            ">=" => ordering.is_ge(),
+            "<=" => ordering.is_le(),
+            ">" => ordering.is_gt(),
+            _ => ordering.is_lt(),
+        }
+    }
+}
+
+/// Documents whose front matter meets every condition, or `None` without conditions.
+/// Numeric values are compared with `doc_meta.number` where the field is a number and as text
+/// where it is not, so that ISO dates such as `revision.0.timestamp>=2020` also compare as expected.
+pub(crate) async fn documents(
+    conditions: &[Condition],
+    pool: &SqlitePool,
+) -> Result<Option<HashSet<String>>, sqlx::Error> {
+    if conditions.is_empty() {
+        return Ok(None);
+    }
+    let mut query = QueryBuilder::<Sqlite>::new("SELECT document FROM doc_info WHERE 1 = 1");
+    for condition in conditions {
+        query.push(" AND document IN (SELECT document FROM doc_meta WHERE key = ");
+        query.push_bind(condition.key.as_str());
+        match (condition.operator, condition.value.parse::<f64>()) {
+            ("^=", _) => {
+                query.push(" AND instr(value, ");
+                query.push_bind(condition.value.as_str());
+                query.push(") = 1");
+            }
+            (operator, Ok(number)) => {
+                query.push(format!(" AND (number {} ", operator));
+                query.push_bind(number);
+                query.push(format!(" OR number IS NULL AND value {} ", operator));
+                query.push_bind(condition.value.as_str());
+                query.push(")");
+            }
+            (operator, Err(_)) => {
+                query.push(format!(" AND value {} ", operator));
+                query.push_bind(condition.value.as_str());
+            }
+        }
+        query.push(")");
+    }
+    let documents = query.build_query_scalar::<String>().fetch_all(pool).await?;
+    Ok(Some(documents.into_iter().collect()))
+}
+
+#[cfg(test)]
+#[test]
+fn test_fields() {
+    let yaml = FrontMatter::Yaml(
+        "title: Saluang\nns: 0\nrevision:\n- id: '42'\n  timestamp: 2020-05-01T10:00:00Z\n  contributor:\n    username: Abu\n    id: null\n"
+            .to_string(),
+    );
+    assert_eq!(
+        yaml.fields().unwrap(),
+        vec![
+            ("title".to_string(), "Saluang".to_string()),
+            ("ns".to_string(), "0".to_string()),
+            ("revision.0.id".to_string(), "42".to_string()),
+            (
+                "revision.0.timestamp".to_string(),
+                "2020-05-01T10:00:00Z".to_string()
+            ),
+            (
+                "revision.0.contributor.username".to_string(),
+                "Abu".to_string()
+            ),
+        ]
+    );
+    let toml = FrontMatter::Toml("title = \"Saluang\"\nyear = 2020\n".to_string());
+    assert_eq!(
+        toml.fields().unwrap(),
+        vec![
+            ("title".to_string(), "Saluang".to_string()),
+            ("year".to_string(), "2020".to_string()),
+        ]
+    );
+    assert!(FrontMatter::Yaml("title: [".to_string()).fields().is_err());
+}
+
+#[cfg(test)]
+#[test]
+fn test_condition() {
+    let condition = |key: &str, operator, value: &str| Condition {
+        key: key.to_string(),
+        operator,
+        value: value.to_string(),
+    };
+    assert_eq!("ns=0".parse(), Ok(condition("ns", "=", "0")));
+    assert_eq!("year >= 2020".parse(), Ok(condition("year", ">=", "2020")));
+    assert_eq!(
+        "category!=Muzik".parse(),
+        Ok(condition("category", "!=", "Muzik"))
+    );
+    assert_eq!("ns<1".parse(), Ok(condition("ns", "<", "1")));
+    assert_eq!("title^=Sal".parse(), Ok(condition("title", "^=", "Sal")));
+    let fields = [
+        ("title".to_string(), "Saluang".to_string()),
+        ("ns".to_string(), "0".to_string()),
+        (
+            "revision.0.timestamp".to_string(),
+            "2021-01-02T00:00:00Z".to_string(),
+        ),
+    ];
+    let matches = |c: &str| c.parse::<Condition>().unwrap().matches(&fields);
+    assert!(matches("ns=0.0"));
+    assert!(matches("ns<1"));
+    assert!(!matches("ns!=0"));
+    assert!(matches("title^=Sal"));
+    assert!(!matches("title^=sal"));
+    assert!(matches("revision.0.timestamp>=2020"));
+    assert!(!matches("year>=2020"));
+    assert!("=0".parse::<Condition>().is_err());
+    assert!("ns".parse::<Condition>().is_err());
+}
+
+#[cfg(test)]
+#[tokio::test]
+async fn test_documents() {
+    let pool = crate::db::memory().await.unwrap();
+    for (document, key, value, number) in [
+        ("a.md", "title", "Saluang", None),
+        ("a.md", "revision.0.timestamp", "2021-01-02T00:00:00Z", None),
+        ("a.md", "ns", "0", Some(0.)),
+        ("b.md", "title", "Seruling", None),
+        ("b.md", "revision.0.timestamp", "2019-05-01T00:00:00Z", None),
+        ("b.md", "ns", "1", Some(1.)),
+        ("c.md", "year", "2020", Some(2020.)),
+    ] {
+        sqlx::query!(
+            "INSERT INTO doc_info (document, term_count, status) VALUES (?, 0, 'complete') ON CONFLICT DO NOTHING",
+            document
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+        sqlx::query!(
+            "INSERT INTO doc_meta (document, key, value, number) VALUES (?, ?, ?, ?)",
+            document,
+            key,
+            value,
+            number
+        )
+        .execute(&pool)
+        .await
+        .unwrap();
+    }
+    let matching = |conditions: &[&str]| {
+        let conditions = conditions
+            .iter()
+            .map(|c| c.parse().unwrap())
+            .collect::<Vec<Condition>>();
+        let pool = pool.clone();
+        async move {
+            let mut documents = documents(&conditions, &pool)
+                .await
+                .unwrap()
+                .unwrap()
+                .into_iter()
+                .collect::<Vec<_>>();
+            documents.sort();
+            documents
+        }
+    };
+    assert_eq!(
+        matching(&["revision.0.timestamp>=2020"]).await,
+        vec!["a.md"]
+    );
+    assert_eq!(matching(&["ns<1"]).await, vec!["a.md"]);
+    assert_eq!(matching(&["ns=0.0"]).await, vec!["a.md"]);
+    assert_eq!(matching(&["year>=2020"]).await, vec!["c.md"]);
+    assert_eq!(matching(&["title^=S"]).await, vec!["a.md", "b.md"]);
+    assert_eq!(matching(&["title^=Ser", "ns=1"]).await, vec!["b.md"]);
+    assert!(matching(&["title^=sal"]).await.is_empty());
+    assert_eq!(documents(&[], &pool).await.unwrap(), None);
+}
@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+
+use itertools::Itertools;
+use sqlx::SqlitePool;
+use tokio_stream::StreamExt;
+
+use crate::{cli, db, metadata, scoring};
+
+/// Running max/mean/stdev of a term's score over the documents it occurs in (Welford's algorithm),
+/// so the whole of `term_info` never has to be held in memory.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct Summary {
+    pub doc_count: i64,
+    /// Number of documents in which the term is the text of a link.
+    pub anchor_count: i64,
+    count: u64,
+    mean: f64,
+    m2: f64,
+    max: f64,
+}
+
+impl Summary {
+    fn push(&mut self, value: f64) {
+        if self.count == 0 || value > self.max {
+            self.max = value;
+        }
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f64;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    pub fn max(&self) -> f64 {
+        self.max
+    }
+
+    pub fn mean(&self) -> f64 {
+        self.mean
+    }
+
+    /// Population standard deviation.
+    pub fn stdev(&self) -> f64 {
+        if self.count == 0 {
+            return 0.;
+        }
+        (self.m2 / self.count as f64).sqrt()
+    }
+
+    fn key(&self, sort: cli::MineSort) -> f64 {
+        match sort {
+            cli::MineSort::Max => self.max(),
+            cli::MineSort::Mean => self.mean(),
+            cli::MineSort::Stdev => self.stdev(),
+            cli::MineSort::Df => self.doc_count as f64,
+            cli::MineSort::Anchors => self.anchor_count as f64,
+        }
+    }
+}
+
+/// Score of every term at `level` with at least `min_df` documents, summarised across documents.
+/// Stopwords are left out, and so are documents outside `documents` when given.
+pub(crate) async fn summarise(
+    scorer: &dyn scoring::Scorer,
+    weights: &scoring::ContextWeights,
+    min_df: i64,
+    level: cli::Level,
+    documents: Option<&HashSet<String>>,
+    pool: &SqlitePool,
+) -> Result<HashMap<String, Summary>, sqlx::Error> {
+    let corpus = scoring::Corpus::load(pool, documents).await?;
+    let within = |document: &String| documents.is_none_or(|d| d.contains(document));
+    let extra = weights.extra(level, None, pool).await?;
+
+    // Rows arrive grouped by term, so each term's postings are scored once they are all in.
+    let level = level.as_str();
+    let mut rows = sqlx::query! {
+        r#"SELECT
+            CASE ? WHEN 'surface' THEN term WHEN 'lemma' THEN COALESCE(lemma, lower) ELSE lower END
+                as "key!: String",
+            term_info.document,
+            SUM(term_info.occurence) as "occurence!: i64",
+            doc_info.term_count
+        FROM term_info
+        JOIN doc_info ON doc_info.document = term_info.document
+        WHERE NOT term_info.is_stop
+        GROUP BY 1, term_info.document
+        ORDER BY 1"#,
+        level
+    }
+    .fetch(pool);
+
+    let mut summaries = HashMap::new();
+    let mut current: Option<String> = None;
+    let mut postings = Vec::new();
+    // Each posting is one document, so the document frequency is known once a term's rows are in.
+    let mut finish = |term: String, postings: &[scoring::Posting]| {
+        if postings.len() as i64 >= min_df {
+            summaries.insert(term, summarise_term(scorer, postings, &corpus));
+        }
+    };
+    while let Some(row) = rows.next().await {
+        let row = row?;
+        if !within(&row.document) {
+            continue;
+        }
+        let occurence =
+            row.occurence as f64 + extra.get(&(row.key.clone(), row.document)).unwrap_or(&0.);
+        if current.as_ref() != Some(&row.key) {
+            if let Some(term) = current.take() {
+                finish(term, &postings);
+            }
+            postings.clear();
+            current = Some(row.key);
+        }
+        postings.push(scoring::Posting {
+            occurence,
+            length: row.term_count,
+        });
+    }
+    if let Some(term) = current {
+        finish(term, &postings);
+    }
+
+    for (term, document) in anchors(level, pool).await? {
+        if !within(&document) {
+            continue;
+        }
+        if let Some(summary) = summaries.get_mut(&term) {
+            summary.anchor_count += 1;
+        }
+    }
+    Ok(summaries)
+}
+
+/// Every one-word anchor, keyed at `level`, and a document linking from it.
+async fn anchors(level: &str, pool: &SqlitePool) -> Result<Vec<(String, String)>, sqlx::Error> {
+    let rows = sqlx::query! {
+        r#"SELECT
+            CASE ? WHEN 'surface' THEN term WHEN 'lemma' THEN COALESCE((
+                SELECT lemma FROM term_info
+                WHERE term_info.document = link_anchor.document AND term_info.lower = link_anchor.lower
+                LIMIT 1
+            ), lower) ELSE lower END as "key!: String",
+            document
+        FROM link_anchor
+        WHERE instr(lower, ' ') = 0
+        GROUP BY 1, document"#,
+        level
+    }
+    .fetch_all(pool)
+    .await?;
+    Ok(rows.into_iter().map(|r| (r.key, r.document)).collect())
+}
+
+fn summarise_term(
+    scorer: &dyn scoring::Scorer,
+    postings: &[scoring::Posting],
+    corpus: &scoring::Corpus,
+) -> Summary {
+    let stats = scoring::TermStats::new(postings, corpus);
+    let mut summary = Summary {
+        doc_count: stats.doc_count,
+        ..Default::default()
+    };
+    for p in postings {
+        summary.push(scorer.score(&stats, p));
+    }
+    summary
+}
+
+pub(crate) async fn mine(arg: cli::Mine) {
+    let pool = db::connect(&arg.database).await.unwrap();
+    let scorer = scoring::scorer(&arg.scoring);
+    let weights = scoring::ContextWeights::new(&arg.scoring);
+    let documents = metadata::documents(&arg.sub_corpus.conditions, &pool)
+        .await
+        .unwrap();
+    let summaries = summarise(
+        scorer.as_ref(),
+        &weights,
+        arg.min_df,
+        arg.level,
+        documents.as_ref(),
+        &pool,
+    )
+    .await
+    .unwrap();
+
+    println!("term\tdf\tmax\tmean\tstdev\tanchors");
+    for (term, summary) in summaries
+        .iter()
+        .sorted_by(|(term_a, a), (term_b, b)| {
+            b.key(arg.sort)
+                .total_cmp(&a.key(arg.sort))
+                .then_with(|| term_a.cmp(term_b))
+        })
+        .take(arg.top)
+    {
+        println!(
+            "{}\t{}\t{:.5}\t{:.5}\t{:.5}\t{}",
+            term,
+            summary.doc_count,
+            summary.max(),
+            summary.mean(),
+            summary.stdev(),
+            summary.anchor_count
+        );
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_summary() {
+    let mut summary = Summary::default();
+    for v in [2., 4., 4., 4., 5., 5., 7., 9.] {
+        summary.push(v);
+    }
+    assert_eq!(summary.max(), 9.);
+    assert!((summary.mean() - 5.).abs() < 1e-12);
+    assert!((summary.stdev() - 2.).abs() < 1e-12);
+}
@@ -1,16 +1,17 @@
-use std::{fs::File, io::Read};
+use std::ops::Range;
 
 use itertools::Itertools;
 use markdown::{mdast::Node, Constructs, ParseOptions};
 use nom::{
-    bytes::complete::tag,
-    character::complete::{alphanumeric1, anychar, char, digit0, multispace1, one_of},
+    bytes::complete::{tag, take_while1},
+    character::complete::{anychar, char, digit0, multispace1, one_of},
     combinator::recognize,
-    multi::many0,
-    sequence::delimited,
+    multi::{many0, many1},
     IResult, Parser as NomParser,
 };
-use sqlx::{sqlite::SqliteQueryResult, Error, SqlitePool};
+use unicode_general_category::{get_general_category, GeneralCategory};
+
+use crate::metadata::FrontMatter;
 
 #[cfg(test)]
 mod test {
@@ -76,37 +77,368 @@ saluang hamba, kononlah anak sidang manusia...')"
         )
         .unwrap();
 
-        let mut collector = Vec::new();
+        let mut collector = crate::parser::Collector::default();
         crate::parser::walk_ast(&mdast, &mut collector);
-        assert!(crate::parser::tokenize(&collector).is_ok())
+        assert!(crate::parser::tokenize(&collector.text).is_ok())
+    }
+
+    #[test]
+    fn test_ngrams() {
+        let collector = vec!["alat muzik tiup, kayu".to_string()];
+        let tokens = crate::parser::tokenize(&collector).unwrap();
+        let terms = |n| {
+            crate::parser::ngrams(&tokens, n)
+                .iter()
+                .map(|ng| ng.term())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(terms(2), vec!["alat muzik", "muzik tiup"]);
+        assert_eq!(terms(3), vec!["alat muzik tiup"]);
+        assert!(terms(4).is_empty());
+    }
+
+    #[test]
+    fn test_link_anchors() {
+        let mdtext = "Saluang ialah [alat muzik](https://ms.wikipedia.org/wiki/Alat_muzik) \
+            [tiup][tiup] ~~kayu~~.\n\n[tiup]: https://ms.wikipedia.org/wiki/Tiup\n";
+        let document = crate::parser::tokenize_document(mdtext.as_bytes().to_vec()).unwrap();
+        let text = document
+            .tokens
+            .iter()
+            .filter(|t| t.is_text())
+            .map(|t| t.unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            text,
+            // Struck-out `kayu` is left out; the label of the definition is kept as text.
+            vec!["Saluang", "ialah", "alat", "muzik", "tiup", "tiup"]
+        );
+        assert_eq!(
+            document.anchors,
+            vec![
+                crate::parser::Anchor {
+                    text: "alat muzik".to_string(),
+                    target: "https://ms.wikipedia.org/wiki/Alat_muzik".to_string(),
+                },
+                crate::parser::Anchor {
+                    text: "tiup".to_string(),
+                    target: "https://ms.wikipedia.org/wiki/Tiup".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_contexts() {
+        use crate::parser::Context;
+        let mdtext = "# Saluang\n\n**Saluang** ialah alat muzik.\n\n- buluh\n\n| kayu |\n|---|\n| talang |\n";
+        let document = crate::parser::tokenize_document(mdtext.as_bytes().to_vec()).unwrap();
+        let contexts = document
+            .tokens
+            .iter()
+            .zip(&document.spans)
+            .filter(|(t, _)| t.is_text())
+            .map(|(t, s)| (t.unwrap(), s.context))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contexts,
+            vec![
+                ("Saluang".to_string(), Context::Heading),
+                ("Saluang".to_string(), Context::Emphasis),
+                ("ialah".to_string(), Context::Body),
+                ("alat".to_string(), Context::Body),
+                ("muzik".to_string(), Context::Body),
+                ("buluh".to_string(), Context::List),
+                ("kayu".to_string(), Context::Table),
+                ("talang".to_string(), Context::Table),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_spans() {
+        let mdtext = "---\ntitle: Saluang\n---\n# Saluang\n\n**Saluang** ialah\nalat [muzik][1] &amp; tiup.\n\n[1]: https://ms.wikipedia.org\n";
+        let document = crate::parser::tokenize_document(mdtext.as_bytes().to_vec()).unwrap();
+        let spans = document
+            .tokens
+            .iter()
+            .zip(&document.spans)
+            .filter(|(t, _)| t.is_text())
+            .map(|(t, s)| (t.unwrap(), &mdtext[s.range.clone()], s.block))
+            .collect::<Vec<_>>();
+        let heading = spans[0].2;
+        let paragraph = spans[1].2;
+        assert_ne!(heading, paragraph);
+        assert_eq!(
+            spans,
+            vec![
+                ("Saluang".to_string(), "Saluang", heading),
+                ("Saluang".to_string(), "Saluang", paragraph),
+                ("ialah".to_string(), "ialah", paragraph),
+                ("alat".to_string(), "alat", paragraph),
+                ("muzik".to_string(), "muzik", paragraph),
+                ("tiup".to_string(), "tiup", paragraph),
+                // The label of the link reference definition
+                ("1".to_string(), "1", paragraph + 1),
+            ]
+        );
+        let spans = &document.spans;
+        assert!(spans
+            .windows(2)
+            .all(|w| w[0].range.start <= w[1].range.start));
+    }
+
+    fn words(text: &str) -> Vec<String> {
+        crate::parser::tokenize(&vec![text.to_string()])
+            .unwrap()
+            .into_iter()
+            .filter(|t| t.is_text())
+            .map(|t| t.unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_unicode_words() {
+        // Jawi, with a harakat and the Arabic comma.
+        assert_eq!(
+            words("ڤرکارا اين سڠت ڤنتيڠ، دان مُدرسه"),
+            vec!["ڤرکارا", "اين", "سڠت", "ڤنتيڠ", "دان", "مُدرسه"]
+        );
+        // Precomposed and decomposed accents.
+        assert_eq!(
+            words("Ṣalāt di kafé, cafe\u{301}"),
+            vec!["Ṣalāt", "di", "kafé", "cafe\u{301}"]
+        );
+        // Apostrophes within a word, reduplication and a quote closing a word.
+        assert_eq!(
+            words("Qur’an dibaca ma'af, kupu-kupu 'terbang'"),
+            vec!["Qur’an", "dibaca", "ma'af", "kupu-kupu", "terbang"]
+        );
+        assert_eq!(
+            words("Pemerintah Indonesia-Malaysia bertemu di Jakarta"),
+            vec![
+                "Pemerintah",
+                "Indonesia-Malaysia",
+                "bertemu",
+                "di",
+                "Jakarta"
+            ]
+        );
+    }
+}
+
+/// Why a document could not be turned into tokens.
+#[derive(Debug)]
+pub(crate) enum ParseError {
+    Read(std::io::Error),
+    Utf8(std::string::FromUtf8Error),
+    Markdown(markdown::message::Message),
+    Metadata(String),
+    Tokenize(String),
+}
+
+impl ParseError {
+    /// Name of the stage that failed, as recorded in `doc_errors.stage`.
+    pub fn stage(&self) -> &'static str {
+        match self {
+            ParseError::Read(_) => "read",
+            ParseError::Utf8(_) => "utf8",
+            ParseError::Markdown(_) => "markdown",
+            ParseError::Metadata(_) => "metadata",
+            ParseError::Tokenize(_) => "tokenize",
+        }
+    }
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Read(err) => write!(f, "cannot read file: {}", err),
+            ParseError::Utf8(err) => write!(f, "file is not valid UTF-8: {}", err),
+            ParseError::Markdown(err) => write!(f, "cannot parse markdown: {}", err),
+            ParseError::Metadata(err) => write!(f, "cannot parse front matter: {}", err),
+            ParseError::Tokenize(rest) => write!(f, "unparsed pattern: {}", rest),
+        }
     }
 }
 
-pub(crate) fn tokenize_file(filename: std::path::PathBuf) -> Vec<Token> {
-    let mut file = File::open(filename).unwrap();
-    let mut buf = String::new();
-    file.read_to_string(&mut buf).unwrap();
+impl std::error::Error for ParseError {}
+
+/// Visible text of a hyperlink and where it points to.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Anchor {
+    pub text: String,
+    /// URL of the link, or the label of a reference link whose definition is missing.
+    pub target: String,
+}
+
+/// Structural element a span of text sits in; the innermost one wins.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub(crate) enum Context {
+    #[default]
+    Body,
+    Heading,
+    /// Emphasis or strong emphasis, which is how Malay Wikipedia introduces a term.
+    Emphasis,
+    Table,
+    List,
+}
+
+impl Context {
+    fn of(node: &Node) -> Option<Self> {
+        match node {
+            Node::Heading(_) => Some(Context::Heading),
+            Node::Emphasis(_) | Node::Strong(_) => Some(Context::Emphasis),
+            Node::Table(_) => Some(Context::Table),
+            Node::List(_) => Some(Context::List),
+            _ => None,
+        }
+    }
+
+    /// Name stored in `term_context.context`.
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Context::Body => "body",
+            Context::Heading => "heading",
+            Context::Emphasis => "emphasis",
+            Context::Table => "table",
+            Context::List => "list",
+        }
+    }
+}
+
+/// Where a span of text, or a token within it, comes from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Span {
+    pub context: Context,
+    /// Byte range in the markdown file.
+    pub range: Range<usize>,
+    /// Index of the paragraph, heading, table cell or other block it sits in.
+    pub block: usize,
+}
+
+/// Tokens of a markdown file, where each token comes from and the anchors of its links.
+#[derive(Debug)]
+pub(crate) struct Document {
+    pub tokens: Vec<Token>,
+    pub spans: Vec<Span>,
+    pub anchors: Vec<Anchor>,
+    /// Flattened fields of the YAML or TOML front matter, or why it could not be parsed.
+    pub metadata: Result<Vec<(String, String)>, ParseError>,
+}
+
+/// Text gathered while walking the markdown tree.
+#[derive(Debug, Default)]
+pub(crate) struct Collector {
+    pub text: Vec<String>,
+    /// Origin of every span of `text`, its range being that of the node it was taken from.
+    pub spans: Vec<Span>,
+    context: Context,
+    block: usize,
+    blocks: usize,
+    pub anchors: Vec<Anchor>,
+    pub front_matter: Option<FrontMatter>,
+    /// Reference-style anchors, resolved against `definitions` once the whole tree is walked.
+    references: Vec<usize>,
+    definitions: std::collections::HashMap<String, String>,
+}
+
+impl Collector {
+    fn push(&mut self, text: String, node: &Node) {
+        self.text.push(text);
+        self.spans.push(Span {
+            context: self.context,
+            range: node
+                .position()
+                .map_or(0..0, |p| p.start.offset..p.end.offset),
+            block: self.block,
+        });
+    }
+
+    /// Runs `walk` with the context of `node`, if it has one, and in a block of its own if it is one.
+    fn within(&mut self, node: &Node, walk: impl FnOnce(&mut Self)) {
+        let (context, block) = (self.context, self.block);
+        if let Some(context) = Context::of(node) {
+            self.context = context;
+        }
+        if matches!(
+            node,
+            Node::Paragraph(_)
+                | Node::Heading(_)
+                | Node::TableCell(_)
+                | Node::Definition(_)
+                | Node::Image(_)
+        ) {
+            self.blocks += 1;
+            self.block = self.blocks;
+        }
+        walk(self);
+        (self.context, self.block) = (context, block);
+    }
+
+    /// Walks the children of a link as ordinary text, remembering them as an anchor.
+    fn anchor(&mut self, children: &[Node], target: String) {
+        let start = self.text.len();
+        for c in children {
+            walk_ast(c, self);
+        }
+        let text = self.text[start..].join(" ");
+        self.anchors.push(Anchor { text, target });
+    }
+
+    fn resolve_references(&mut self) {
+        for i in self.references.drain(..) {
+            let anchor = &mut self.anchors[i];
+            if let Some(url) = self.definitions.get(&anchor.target) {
+                anchor.target = url.clone();
+            }
+        }
+    }
+}
+
+/// Tokenizes the raw contents of a markdown file.
+pub(crate) fn tokenize_document(bytes: Vec<u8>) -> Result<Document, ParseError> {
+    let buf = String::from_utf8(bytes).map_err(ParseError::Utf8)?;
     let mdast = markdown::to_mdast(
         &buf,
         &ParseOptions {
             constructs: Constructs {
                 frontmatter: true,
+                gfm_table: true,
+                // Struck-out text is parsed as such so that it is left out, not indexed with its tildes.
+                gfm_strikethrough: true,
                 ..Default::default()
             },
+            gfm_strikethrough_single_tilde: false,
             ..Default::default()
         },
     )
-    .unwrap();
-    let mut collector = Vec::new();
+    .map_err(ParseError::Markdown)?;
+    let mut collector = Collector::default();
     walk_ast(&mdast, &mut collector);
-    tokenize(&collector).unwrap()
+    collector.resolve_references();
+    let (tokens, spans) = tokenize_spans(&collector.text, &collector.spans, &buf)?;
+    let metadata = match &collector.front_matter {
+        Some(front_matter) => front_matter.fields(),
+        None => Ok(Vec::new()),
+    };
+    Ok(Document {
+        tokens,
+        spans,
+        anchors: collector.anchors,
+        metadata,
+    })
+}
+fn match_node(node: &Node, collector: &mut Collector) {
+    collector.within(node, |collector| match_children(node, collector));
 }
-fn match_node(node: &Node, collector: &mut Vec<String>) {
+fn match_children(node: &Node, collector: &mut Collector) {
     match node {
+        // Kept apart from the text
+        Node::Yaml(yaml) => collector.front_matter = Some(FrontMatter::Yaml(yaml.value.clone())),
+        Node::Toml(toml) => collector.front_matter = Some(FrontMatter::Toml(toml.value.clone())),
         // Ignore
-        Node::Yaml(_)
-        | Node::Toml(_)
-        | Node::Html(_)
+        Node::Html(_)
         | Node::MdxJsxFlowElement(_)
         | Node::MdxjsEsm(_)
         | Node::MdxFlowExpression(_)
@@ -120,24 +452,31 @@ fn match_node(node: &Node, collector: &mut Vec<String>) {
         | Node::ImageReference(_)
         | Node::Code(_)
         | Node::InlineCode(_)
-        | Node::Link(_)
-        | Node::LinkReference(_)
         | Node::Delete(_) => {}
         // Pushes to collector
         Node::Text(text) => {
-            collector.push(text.value.to_owned());
+            collector.push(text.value.to_owned(), node);
         }
         Node::Image(image) => {
             if let Some(title) = &image.title {
-                collector.push(title.clone());
+                collector.push(title.clone(), node);
             }
         }
+        // Link text is text, and also recorded as an anchor
+        Node::Link(link) => collector.anchor(&link.children, link.url.clone()),
+        Node::LinkReference(reference) => {
+            collector.references.push(collector.anchors.len());
+            collector.anchor(&reference.children, reference.identifier.clone());
+        }
         Node::Definition(definition) => {
+            collector
+                .definitions
+                .insert(definition.identifier.clone(), definition.url.clone());
             if let Some(c) = &definition.label {
-                collector.push(c.clone());
+                collector.push(c.clone(), node);
             }
             if let Some(c) = &definition.title {
-                collector.push(c.clone());
+                collector.push(c.clone(), node);
             }
         }
         // Continue walking ast
@@ -204,69 +543,27 @@ fn match_node(node: &Node, collector: &mut Vec<String>) {
     }
 }
 
-fn walk_ast(ast: &Node, collector: &mut Vec<String>) {
-    match ast.children() {
-        Some(nodes) => {
+fn walk_ast(ast: &Node, collector: &mut Collector) {
+    match (ast, ast.children()) {
+        // Matched whole so that their text is also recorded as an anchor, or struck-out text left out.
+        (Node::Link(_) | Node::LinkReference(_) | Node::Delete(_), _) => match_node(ast, collector),
+        (_, Some(nodes)) => collector.within(ast, |collector| {
             for node in nodes {
                 match_node(node, collector);
             }
-        }
-        None => match_node(ast, collector),
+        }),
+        (_, None) => match_node(ast, collector),
     }
 }
 
+/// A window of consecutive tokens.
 #[derive(Debug, Clone)]
-pub(crate) struct NGram<T>(T);
+pub(crate) struct NGram<'a>(pub &'a [Token]);
 
-impl<T> From<T> for NGram<T> {
-    fn from(value: T) -> Self {
-        NGram(value)
-    }
-}
-
-impl NGram<(&Token, &Token)> {
-    pub async fn register(
-        &self,
-        document: &str,
-        pool: &SqlitePool,
-    ) -> Result<SqliteQueryResult, Error> {
-        let k1 = self.0 .0.unwrap();
-        let k2 = self.0 .1.unwrap();
-        let lower1 = k1.to_lowercase();
-        let lower2 = k2.to_lowercase();
-        let k = format!("{} {}", k1, k2);
-        sqlx::query! {
-            r#" INSERT INTO ngram_two (document, term, lower1, lower2, occurence) VALUES (?, ?, ?, ?, 1)
-            ON CONFLICT
-                DO UPDATE SET occurence = 1 + occurence"#,
-            document, k, lower1, lower2
-        }
-        .execute(pool)
-        .await
-    }
-}
-
-impl NGram<(&Token, &Token, &Token)> {
-    pub async fn register(
-        &self,
-        document: &str,
-        pool: &SqlitePool,
-    ) -> Result<SqliteQueryResult, Error> {
-        let k1 = self.0 .0.unwrap();
-        let k2 = self.0 .1.unwrap();
-        let k3 = self.0 .2.unwrap();
-        let lower1 = k1.to_lowercase();
-        let lower2 = k2.to_lowercase();
-        let lower3 = k3.to_lowercase();
-        let k = format!("{} {} {}", k1, k2, k3);
-        sqlx::query! {
-            r#" INSERT INTO ngram_three (document, term, lower1, lower2, lower3, occurence) VALUES (?, ?, ?, ?, ?, 1)
-            ON CONFLICT
-                DO UPDATE SET occurence = 1 + occurence"#,
-        document, k, lower1, lower2, lower3
-        }
-        .execute(pool)
-        .await
+impl NGram<'_> {
+    /// Surface form, words joined by a space.
+    pub fn term(&self) -> String {
+        self.0.iter().map(Token::unwrap).join(" ")
     }
 }
 
@@ -276,6 +573,10 @@ pub(crate) enum Token {
     Punct(String),
     Unknown(String),
     Omit(String),
+    /// A word whose clitics were split off, kept as a term but left out of n-grams.
+    Surface(String),
+    /// A function word, counted in the document length but breaking n-gram windows.
+    Stop(String),
 }
 
 impl Token {
@@ -286,27 +587,20 @@ impl Token {
         }
     }
 
-    fn unwrap(&self) -> String {
-        match self {
-            Token::Text(a) | Token::Punct(a) | Token::Unknown(a) | Token::Omit(a) => a.clone(),
-        }
+    /// Whether the token is counted as a term.
+    pub fn is_term(&self) -> bool {
+        matches!(self, Token::Text(_) | Token::Surface(_) | Token::Stop(_))
     }
 
-    pub async fn register(
-        &self,
-        document: &str,
-        pool: &SqlitePool,
-    ) -> Result<SqliteQueryResult, Error> {
-        let k = self.unwrap();
-        let lower = k.to_lowercase();
-        sqlx::query! {
-            r#" INSERT INTO term_info (document, term, lower, occurence) VALUES (?, ?, ?, 1)
-                        ON CONFLICT
-                            DO UPDATE SET occurence = 1 + occurence"#,
-            document, k, lower
+    pub fn unwrap(&self) -> String {
+        match self {
+            Token::Text(a)
+            | Token::Punct(a)
+            | Token::Unknown(a)
+            | Token::Omit(a)
+            | Token::Surface(a)
+            | Token::Stop(a) => a.clone(),
         }
-        .execute(pool)
-        .await
     }
 }
 
@@ -327,12 +621,41 @@ impl From<String> for Token {
     }
 }
 
-const PUNCTS: &str = ".,;:–/()\"[]'*|=-{}’%!";
+const PUNCTS: &str = ".,;:–/()\"[]'*|=-{}’%!،؛؟";
+/// Apostrophes that may join two parts of one word, as in `Qur’an` or `ma'af`.
+const APOSTROPHES: &str = "'’ʼ";
 fn punctuation(input: &str) -> IResult<&str, &str> {
     recognize(one_of(PUNCTS)).parse(input)
 }
+/// Letters and numbers of any script, combining marks (harakat, decomposed accents),
+/// connectors and the zero-width (non-)joiners used in Arabic script.
+fn is_word_char(c: char) -> bool {
+    use GeneralCategory::*;
+    matches!(
+        get_general_category(c),
+        UppercaseLetter
+            | LowercaseLetter
+            | TitlecaseLetter
+            | ModifierLetter
+            | OtherLetter
+            | NonspacingMark
+            | SpacingMark
+            | EnclosingMark
+            | DecimalNumber
+            | LetterNumber
+            | ConnectorPunctuation
+    ) || c == '\u{200C}'
+        || c == '\u{200D}'
+}
+fn word(input: &str) -> IResult<&str, &str> {
+    recognize((
+        take_while1(is_word_char),
+        many0((one_of(APOSTROPHES), take_while1(is_word_char))),
+    ))
+    .parse(input)
+}
 fn kata_ganda(input: &str) -> IResult<&str, &str> {
-    recognize(delimited(alphanumeric1, char('-'), alphanumeric1)).parse(input)
+    recognize((word, many1((char('-'), word)))).parse(input)
 }
 
 #[cfg(test)]
@@ -351,7 +674,7 @@ fn markup_elem(input: &str) -> IResult<&str, &str> {
 }
 fn known_pattern(input: &str) -> IResult<&str, &str> {
     kata_ganda
-        .or(alphanumeric1)
+        .or(word)
         .or(recognize(multispace1))
         .or(punctuation)
         .parse(input)
@@ -359,36 +682,74 @@ fn known_pattern(input: &str) -> IResult<&str, &str> {
 fn parse(input: &str) -> IResult<&str, Vec<&str>> {
     many0(markup_elem.or(known_pattern).or(recognize(anychar))).parse(input)
 }
-fn tokenize(collector: &Vec<String>) -> Result<Vec<Token>, String> {
+pub(crate) fn tokenize(collector: &Vec<String>) -> Result<Vec<Token>, ParseError> {
     let input = collector.join(" ");
-    let (rest, output) = parse(&input).unwrap();
+    Ok(parse_tokens(&input)?
+        .into_iter()
+        .map(|(_, token)| token)
+        .collect())
+}
+/// Tokens of `input` with the byte range each spans.
+fn parse_tokens(input: &str) -> Result<Vec<(Range<usize>, Token)>, ParseError> {
+    let (rest, output) = parse(input).map_err(|e| ParseError::Tokenize(e.to_string()))?;
     if !rest.trim().is_empty() {
-        return Err(format!("Unparsed pattern: {}", rest));
+        return Err(ParseError::Tokenize(rest.to_string()));
     }
     Ok(output
         .iter()
         .filter(|a| !a.trim().is_empty())
-        .map(|a| Token::from(a.to_string()))
+        .map(|a| {
+            let offset = a.as_ptr() as usize - input.as_ptr() as usize;
+            (offset..offset + a.len(), Token::from(a.to_string()))
+        })
         .collect())
 }
 
-pub(crate) fn ngram2(items: &Vec<Token>) -> Vec<NGram<(&Token, &Token)>> {
-    let mut res = Vec::new();
-    for it in items.iter().tuple_windows::<(_, _)>() {
-        match it {
-            (Token::Text(_), Token::Text(_)) => res.push(NGram::from(it)),
-            _ => continue,
-        }
-    }
-    res
-}
-pub(crate) fn ngram3(items: &Vec<Token>) -> Vec<NGram<(&Token, &Token, &Token)>> {
-    let mut res = Vec::new();
-    for it in items.iter().tuple_windows::<(_, _, _)>() {
-        match it {
-            (Token::Text(_), Token::Text(_), Token::Text(_)) => res.push(NGram::from(it)),
-            _ => continue,
-        }
-    }
-    res
+/// Tokenizes the texts like [`tokenize`], giving each token the span its text starts in narrowed
+/// down to where the token is found in `source`. A token that cannot be found there, such as a
+/// character written as an entity, keeps the rest of the range of its node.
+fn tokenize_spans(
+    texts: &[String],
+    spans: &[Span],
+    source: &str,
+) -> Result<(Vec<Token>, Vec<Span>), ParseError> {
+    let starts = texts
+        .iter()
+        .scan(0, |start, text| {
+            let current = *start;
+            *start += text.len() + 1;
+            Some(current)
+        })
+        .collect::<Vec<_>>();
+    let input = texts.join(" ");
+    // Where the previous token ended in `source`, so that ranges never go backwards.
+    let mut cursor = 0;
+    Ok(parse_tokens(&input)?
+        .into_iter()
+        .map(|(range, token)| {
+            let mut span = spans[starts.partition_point(|start| *start <= range.start) - 1].clone();
+            let text = &input[range];
+            cursor = cursor.max(span.range.start);
+            let found = source
+                .get(cursor..span.range.end)
+                .and_then(|node| node.find(text));
+            span.range = match found {
+                Some(at) => cursor + at..cursor + at + text.len(),
+                None => cursor..span.range.end.max(cursor),
+            };
+            if found.is_some() {
+                cursor = span.range.end;
+            }
+            (token, span)
+        })
+        .unzip())
+}
+
+/// Every window of `n` tokens made only of `Token::Text`.
+pub(crate) fn ngrams(items: &[Token], n: usize) -> Vec<NGram<'_>> {
+    items
+        .windows(n)
+        .filter(|window| window.iter().all(Token::is_text))
+        .map(NGram)
+        .collect()
 }
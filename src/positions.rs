@@ -0,0 +1,279 @@
+//! Positional index: where every word of a document occurs, stored per (document, term)
+//! as a delta-encoded blob so that phrases and concordances need no re-parsing.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+
+use crate::{cli, clitic, db, jawi, metadata, parser, stemmer};
+
+/// One occurrence of a word.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Position {
+    /// Index among the running words of the document, stopwords included.
+    pub offset: u64,
+    /// Byte span of the word in the markdown file.
+    pub start: u64,
+    pub end: u64,
+    /// Index of the sentence, counted by sentence-ending punctuation and block boundaries.
+    pub sentence: u64,
+}
+
+fn write_varint(mut n: u64, bytes: &mut Vec<u8>) {
+    while n >= 0x80 {
+        bytes.push((n as u8 & 0x7F) | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &mut impl Iterator<Item = u8>) -> Option<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let byte = bytes.next()?;
+        n |= ((byte & 0x7F) as u64) << shift;
+        if byte & 0x80 == 0 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+/// Encodes positions sorted by offset as LEB128 varints: the offset, start and sentence as
+/// differences from the previous position, and the span as its length.
+pub(crate) fn encode(positions: &[Position]) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut previous = Position {
+        offset: 0,
+        start: 0,
+        end: 0,
+        sentence: 0,
+    };
+    for p in positions {
+        write_varint(p.offset - previous.offset, &mut bytes);
+        write_varint(p.start - previous.start, &mut bytes);
+        write_varint(p.end - p.start, &mut bytes);
+        write_varint(p.sentence - previous.sentence, &mut bytes);
+        previous = *p;
+    }
+    bytes
+}
+
+/// Inverse of [`encode`]; a truncated blob yields the positions before the truncation.
+pub(crate) fn decode(bytes: &[u8]) -> Vec<Position> {
+    let mut bytes = bytes.iter().copied();
+    let mut positions = Vec::new();
+    let (mut offset, mut start, mut sentence) = (0, 0, 0);
+    while let Some(delta) = read_varint(&mut bytes) {
+        let (Some(start_delta), Some(length), Some(sentence_delta)) = (
+            read_varint(&mut bytes),
+            read_varint(&mut bytes),
+            read_varint(&mut bytes),
+        ) else {
+            break;
+        };
+        offset += delta;
+        start += start_delta;
+        sentence += sentence_delta;
+        positions.push(Position {
+            offset,
+            start,
+            end: start + length,
+            sentence,
+        });
+    }
+    positions
+}
+
+/// Offsets at which the words of a phrase follow each other, given the positions of each word.
+pub(crate) fn phrase_offsets(words: &[Vec<Position>]) -> Vec<u64> {
+    let Some((first, rest)) = words.split_first() else {
+        return Vec::new();
+    };
+    let rest = rest
+        .iter()
+        .map(|positions| positions.iter().map(|p| p.offset).collect::<HashSet<_>>())
+        .collect::<Vec<_>>();
+    first
+        .iter()
+        .map(|p| p.offset)
+        .filter(|offset| {
+            rest.iter()
+                .enumerate()
+                .all(|(i, offsets)| offsets.contains(&(offset + i as u64 + 1)))
+        })
+        .collect()
+}
+
+/// Offsets at which the phrase of `keys` starts in every document containing it.
+pub(crate) async fn find_phrase(
+    keys: &[String],
+    pool: &SqlitePool,
+) -> Result<BTreeMap<String, Vec<u64>>, sqlx::Error> {
+    if keys.is_empty() {
+        return Ok(BTreeMap::new());
+    }
+    let mut query = QueryBuilder::<Sqlite>::new(
+        "SELECT document, lower, positions FROM term_position WHERE lower IN (",
+    );
+    let mut separated = query.separated(", ");
+    for key in keys.iter().collect::<HashSet<_>>() {
+        separated.push_bind(key.as_str());
+    }
+    separated.push_unseparated(")");
+    let rows = query
+        .build_query_as::<(String, String, Vec<u8>)>()
+        .fetch_all(pool)
+        .await?;
+
+    // Surface forms sharing a key are one word of the phrase.
+    let mut documents: HashMap<String, HashMap<String, Vec<Position>>> = HashMap::new();
+    for (document, lower, positions) in rows {
+        documents
+            .entry(document)
+            .or_default()
+            .entry(lower)
+            .or_default()
+            .extend(decode(&positions));
+    }
+    Ok(documents
+        .into_iter()
+        .filter_map(|(document, words)| {
+            let words = keys
+                .iter()
+                .map(|key| words.get(key).cloned().unwrap_or_default())
+                .collect::<Vec<_>>();
+            let mut offsets = phrase_offsets(&words);
+            offsets.sort_unstable();
+            (!offsets.is_empty()).then_some((document, offsets))
+        })
+        .collect())
+}
+
+/// Every word of `document` by offset, rebuilt from the positional index.
+pub(crate) async fn words(
+    document: &str,
+    pool: &SqlitePool,
+) -> Result<BTreeMap<u64, (String, Position)>, sqlx::Error> {
+    let rows =
+        sqlx::query! {"SELECT term, positions FROM term_position WHERE document = ?", document}
+            .fetch_all(pool)
+            .await?;
+    Ok(rows
+        .into_iter()
+        .flat_map(|row| {
+            decode(&row.positions)
+                .into_iter()
+                .map(move |p| (p.offset, (row.term.clone(), p)))
+        })
+        .collect())
+}
+
+pub(crate) async fn concordance(arg: cli::Concordance) {
+    let pool = db::connect(&arg.database).await.unwrap();
+    let jawi = arg
+        .jawi
+        .transliterate_jawi
+        .then(|| jawi::Transliterator::load(arg.jawi.jawi_dictionary.as_deref()).unwrap());
+    // The phrase is split at its clitics like the documents were, so `bukunya` finds `buku nya`.
+    let mut tokens = parser::tokenize(&vec![arg.phrase.clone()]).unwrap();
+    if !arg.no_clitic_split {
+        let stemmer = stemmer::Stemmer::load(arg.stemming.roots.as_deref()).unwrap();
+        tokens = clitic::split(tokens, false, &stemmer);
+    }
+    let keys = tokens
+        .iter()
+        .filter(|t| t.is_text())
+        .map(|t| {
+            let word = t.unwrap();
+            jawi.as_ref()
+                .and_then(|jawi| jawi.to_rumi(&word))
+                .unwrap_or_else(|| word.to_lowercase())
+        })
+        .collect::<Vec<_>>();
+    let length = keys.len() as u64;
+
+    let matches = find_phrase(&keys, &pool).await.unwrap();
+    let documents = metadata::documents(&arg.sub_corpus.conditions, &pool)
+        .await
+        .unwrap();
+
+    println!("document\tsentence\tstart\tend\tleft\tphrase\tright");
+    for (document, offsets) in matches {
+        if documents.as_ref().is_some_and(|d| !d.contains(&document)) {
+            continue;
+        }
+        let words = words(&document, &pool).await.unwrap();
+        let join = |from: u64, to: u64| {
+            words
+                .range(from..to)
+                .map(|(_, (term, _))| term.as_str())
+                .collect::<Vec<_>>()
+                .join(" ")
+        };
+        for offset in offsets {
+            let first = words[&offset].1;
+            let last = words[&(offset + length - 1)].1;
+            println!(
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                document,
+                first.sentence,
+                first.start,
+                last.end,
+                join(offset.saturating_sub(arg.width), offset),
+                join(offset, offset + length),
+                join(offset + length, offset + length + arg.width),
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+#[test]
+fn test_encode_decode() {
+    let positions = vec![
+        Position {
+            offset: 0,
+            start: 0,
+            end: 7,
+            sentence: 0,
+        },
+        Position {
+            offset: 150,
+            start: 1_000,
+            end: 1_007,
+            sentence: 9,
+        },
+        Position {
+            offset: 151,
+            start: 100_000,
+            end: 100_300,
+            sentence: 9,
+        },
+    ];
+    let bytes = encode(&positions);
+    assert!(bytes.len() < 3 * 4 * 2);
+    assert_eq!(decode(&bytes), positions);
+    assert_eq!(decode(&bytes[..bytes.len() - 1]), positions[..2]);
+}
+
+#[cfg(test)]
+#[test]
+fn test_phrase_offsets() {
+    let at = |offsets: &[u64]| {
+        offsets
+            .iter()
+            .map(|&offset| Position {
+                offset,
+                start: 0,
+                end: 0,
+                sentence: 0,
+            })
+            .collect::<Vec<_>>()
+    };
+    let alat = at(&[3, 10, 20]);
+    let muzik = at(&[4, 12, 21]);
+    let tiup = at(&[5, 22]);
+    assert_eq!(phrase_offsets(&[alat.clone(), muzik.clone()]), vec![3, 20]);
+    assert_eq!(phrase_offsets(&[alat, muzik, tiup]), vec![3, 20]);
+}
@@ -0,0 +1,318 @@
+use std::collections::{HashMap, HashSet};
+
+use sqlx::SqlitePool;
+
+use crate::cli;
+
+/// Occurrences of one (lowercase) term in one document.
+#[derive(Debug, Clone)]
+pub(crate) struct Posting {
+    /// Weighted by the structural context of each occurrence, see [`ContextWeights`].
+    pub occurence: f64,
+    /// `doc_info.term_count` of the document.
+    pub length: i64,
+}
+
+impl Posting {
+    pub fn frequency(&self) -> f64 {
+        if self.length == 0 {
+            return 0.;
+        }
+        self.occurence / self.length as f64
+    }
+}
+
+/// Collection-wide statistics shared by every term.
+#[derive(Debug, Clone)]
+pub(crate) struct Corpus {
+    pub documents: u64,
+    pub average_length: f64,
+}
+
+impl Corpus {
+    /// Statistics of the whole corpus, or only of `documents` when given.
+    pub async fn load(
+        pool: &SqlitePool,
+        documents: Option<&HashSet<String>>,
+    ) -> Result<Self, sqlx::Error> {
+        if let Some(documents) = documents {
+            let lengths = sqlx::query! {"SELECT document, term_count FROM doc_info"}
+                .fetch_all(pool)
+                .await?
+                .into_iter()
+                .filter(|row| documents.contains(&row.document))
+                .map(|row| row.term_count)
+                .collect::<Vec<_>>();
+            return Ok(Corpus {
+                documents: lengths.len() as u64,
+                average_length: if lengths.is_empty() {
+                    0.
+                } else {
+                    lengths.iter().sum::<i64>() as f64 / lengths.len() as f64
+                },
+            });
+        }
+        let row = sqlx::query! {
+            r#"SELECT COUNT(document) as count, AVG(term_count) as "average: f64" FROM doc_info"#
+        }
+        .fetch_one(pool)
+        .await?;
+        Ok(Corpus {
+            documents: row.count as u64,
+            average_length: row.average.unwrap_or(0.),
+        })
+    }
+}
+
+/// `term_doc_info.doc_count` of a lowercase form, kept up to date by the ingestion triggers.
+pub(crate) async fn document_frequency(lower: &str, pool: &SqlitePool) -> Result<i64, sqlx::Error> {
+    Ok(
+        sqlx::query_scalar! {"SELECT doc_count FROM term_doc_info WHERE term = ?", lower}
+            .fetch_optional(pool)
+            .await?
+            .unwrap_or(0),
+    )
+}
+
+/// Statistics of one term over all of its postings, computed once before scoring each posting.
+#[derive(Debug, Clone)]
+pub(crate) struct TermStats {
+    pub corpus: Corpus,
+    /// Number of documents the term occurs in.
+    pub doc_count: i64,
+    /// Global weight of log-entropy: 1 + Σ p log p / log N, with p the share of the term's
+    /// occurrences falling in each document.
+    pub entropy: f64,
+    /// Juilland's D over the term's relative frequency in every document, including those it is absent from.
+    pub dispersion: f64,
+}
+
+impl TermStats {
+    pub fn new(postings: &[Posting], corpus: &Corpus) -> Self {
+        let n = corpus.documents.max(postings.len() as u64) as f64;
+        let total = postings.iter().map(|p| p.occurence).sum::<f64>();
+
+        let entropy = if n > 1. && total > 0. {
+            let sum = postings
+                .iter()
+                .map(|p| p.occurence / total)
+                .filter(|p| *p > 0.)
+                .map(|p| p * p.ln())
+                .sum::<f64>();
+            1. + sum / n.ln()
+        } else {
+            1.
+        };
+
+        let dispersion = if n > 1. {
+            let mean = postings.iter().map(|p| p.frequency()).sum::<f64>() / n;
+            let variance =
+                postings.iter().map(|p| p.frequency().powi(2)).sum::<f64>() / n - mean.powi(2);
+            if mean > 0. {
+                let variation = variance.max(0.).sqrt() / mean;
+                1. - variation / (n - 1.).sqrt()
+            } else {
+                0.
+            }
+        } else {
+            1.
+        };
+
+        TermStats {
+            corpus: corpus.clone(),
+            doc_count: postings.len() as i64,
+            entropy,
+            dispersion,
+        }
+    }
+
+    /// log10(N / df), never negative since a term occurs in at most every document.
+    pub fn idf(&self) -> f64 {
+        if self.doc_count == 0 {
+            return 0.;
+        }
+        (self.corpus.documents as f64 / self.doc_count as f64)
+            .log10()
+            .max(0.)
+    }
+}
+
+/// Weighs one posting of a term; higher means more characteristic of the document.
+pub(crate) trait Scorer: Send + Sync {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64;
+}
+
+/// Relative term frequency times log10(N / df).
+pub(crate) struct TfIdf;
+
+impl Scorer for TfIdf {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64 {
+        posting.frequency() * term.idf()
+    }
+}
+
+/// (1 + ln tf) times log10(N / df), damping terms repeated many times in one document.
+pub(crate) struct SublinearTfIdf;
+
+impl Scorer for SublinearTfIdf {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64 {
+        if posting.occurence <= 0. {
+            return 0.;
+        }
+        (1. + posting.occurence.ln()) * term.idf()
+    }
+}
+
+/// Okapi BM25 with the non-negative idf ln(1 + (N - df + 0.5) / (df + 0.5)).
+pub(crate) struct Bm25 {
+    pub k1: f64,
+    pub b: f64,
+}
+
+impl Scorer for Bm25 {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64 {
+        let n = term.corpus.documents as f64;
+        let df = term.doc_count as f64;
+        let idf = (1. + (n - df + 0.5) / (df + 0.5)).ln();
+        let tf = posting.occurence;
+        let length = if term.corpus.average_length > 0. {
+            posting.length as f64 / term.corpus.average_length
+        } else {
+            1.
+        };
+        idf * tf * (self.k1 + 1.) / (tf + self.k1 * (1. - self.b + self.b * length))
+    }
+}
+
+/// log2(1 + tf) times the term's entropy-based global weight.
+pub(crate) struct LogEntropy;
+
+impl Scorer for LogEntropy {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64 {
+        (1. + posting.occurence).log2() * term.entropy
+    }
+}
+
+/// Relative term frequency times Juilland's D, favouring terms spread evenly over the corpus.
+pub(crate) struct Juilland;
+
+impl Scorer for Juilland {
+    fn score(&self, term: &TermStats, posting: &Posting) -> f64 {
+        posting.frequency() * term.dispersion
+    }
+}
+
+/// How many times an occurrence counts in each structural context; body text counts once.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ContextWeights {
+    pub heading: f64,
+    pub emphasis: f64,
+    pub table: f64,
+    pub list: f64,
+}
+
+impl ContextWeights {
+    pub fn new(arg: &cli::Scoring) -> Self {
+        ContextWeights {
+            heading: arg.heading_weight,
+            emphasis: arg.emphasis_weight,
+            table: arg.table_weight,
+            list: arg.list_weight,
+        }
+    }
+
+    fn is_neutral(&self) -> bool {
+        [self.heading, self.emphasis, self.table, self.list] == [1.; 4]
+    }
+
+    /// What the weights add to the plain occurrence count of each term in each document,
+    /// keyed by the term at `level` (or only `key`, if given) and the document. Documents
+    /// ingested before structural contexts were recorded get nothing added.
+    pub async fn extra(
+        &self,
+        level: cli::Level,
+        key: Option<&str>,
+        pool: &SqlitePool,
+    ) -> Result<HashMap<(String, String), f64>, sqlx::Error> {
+        if self.is_neutral() {
+            return Ok(HashMap::new());
+        }
+        let level = level.as_str();
+        let rows = sqlx::query! {
+            r#"SELECT key as "key!: String", document as "document!: String", extra as "extra!: f64"
+            FROM (
+                SELECT
+                    CASE ?1 WHEN 'surface' THEN term_info.term WHEN 'lemma' THEN COALESCE(term_info.lemma, term_info.lower) ELSE term_info.lower END
+                        as key,
+                    term_context.document as document,
+                    SUM(term_context.occurence * (
+                        CASE term_context.context WHEN 'heading' THEN ?2 WHEN 'emphasis' THEN ?3 WHEN 'table' THEN ?4 ELSE ?5 END - 1.0
+                    )) as extra
+                FROM term_context
+                JOIN term_info ON term_info.document = term_context.document AND term_info.term = term_context.term
+                GROUP BY 1, 2
+            )
+            WHERE ?6 IS NULL OR key = ?6"#,
+            level, self.heading, self.emphasis, self.table, self.list, key
+        }
+        .fetch_all(pool)
+        .await?;
+        Ok(rows
+            .into_iter()
+            .map(|r| ((r.key, r.document), r.extra))
+            .collect())
+    }
+}
+
+pub(crate) fn scorer(arg: &cli::Scoring) -> Box<dyn Scorer> {
+    match arg.scorer {
+        cli::ScorerKind::TfIdf => Box::new(TfIdf),
+        cli::ScorerKind::SublinearTfIdf => Box::new(SublinearTfIdf),
+        cli::ScorerKind::Bm25 => Box::new(Bm25 {
+            k1: arg.k1,
+            b: arg.b,
+        }),
+        cli::ScorerKind::LogEntropy => Box::new(LogEntropy),
+        cli::ScorerKind::Juilland => Box::new(Juilland),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn corpus(documents: u64) -> Corpus {
+        Corpus {
+            documents,
+            average_length: 10.,
+        }
+    }
+
+    #[test]
+    fn test_even_term_is_fully_dispersed() {
+        let postings = vec![
+            Posting {
+                occurence: 2.,
+                length: 10,
+            };
+            4
+        ];
+        let stats = TermStats::new(&postings, &corpus(4));
+        assert!((stats.dispersion - 1.).abs() < 1e-12);
+        assert!(stats.entropy.abs() < 1e-12);
+        assert_eq!(stats.idf(), 0.);
+        assert!(Bm25 { k1: 1.2, b: 0.75 }.score(&stats, &postings[0]) > 0.);
+    }
+
+    #[test]
+    fn test_concentrated_term() {
+        let postings = vec![Posting {
+            occurence: 5.,
+            length: 10,
+        }];
+        let stats = TermStats::new(&postings, &corpus(4));
+        assert!(stats.dispersion.abs() < 1e-12);
+        assert!((stats.entropy - 1.).abs() < 1e-12);
+        assert!((TfIdf.score(&stats, &postings[0]) - 0.5 * 4f64.log10()).abs() < 1e-12);
+    }
+}
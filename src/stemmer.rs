@@ -0,0 +1,154 @@
+use std::{collections::HashSet, path::Path};
+
+/// Roots used when no `--roots` file is given.
+const ROOTS: &str = include_str!("../dictionaries/malay-roots.txt");
+
+/// Derivational suffixes, tried before any prefix is removed so that `memberikan` reaches `beri`.
+const SUFFIXES: [&str; 3] = ["kan", "an", "i"];
+
+/// How many prefixes may be stacked on a root, as in `mem-per-`.
+pub(crate) const MAX_PREFIXES: usize = 3;
+
+/// Rule-based Malay stemmer that only accepts a candidate stem if it is a known root.
+#[derive(Debug)]
+pub(crate) struct Stemmer {
+    roots: HashSet<String>,
+}
+
+impl Stemmer {
+    pub fn new(roots: HashSet<String>) -> Self {
+        Stemmer { roots }
+    }
+
+    /// Reads the root dictionary at `path`, in the same format as a stopword list,
+    /// or the bundled one without a path.
+    pub fn load(path: Option<&Path>) -> std::io::Result<Self> {
+        let roots = match path {
+            Some(path) => crate::stopwords::load(path)?,
+            None => crate::stopwords::parse(ROOTS),
+        };
+        Ok(Stemmer::new(roots))
+    }
+
+    /// Root of `word`, or its lowercase form when no root can be reached.
+    pub fn lemma(&self, word: &str) -> String {
+        let lower = word.to_lowercase();
+        self.root(&lower).unwrap_or(lower)
+    }
+
+    /// Whether a root can be reached from `word`.
+    pub fn is_known(&self, word: &str) -> bool {
+        self.root(&word.to_lowercase()).is_some()
+    }
+
+    fn root(&self, word: &str) -> Option<String> {
+        if self.roots.contains(word) {
+            return Some(word.to_string());
+        }
+        let unsuffixed = SUFFIXES
+            .iter()
+            .filter_map(|suffix| word.strip_suffix(suffix))
+            .chain([word]);
+        for stem in unsuffixed {
+            if self.roots.contains(stem) {
+                return Some(stem.to_string());
+            }
+            if let Some(root) = self.unprefix(stem, MAX_PREFIXES) {
+                return Some(root);
+            }
+        }
+        None
+    }
+
+    fn unprefix(&self, word: &str, depth: usize) -> Option<String> {
+        if depth == 0 {
+            return None;
+        }
+        for candidate in prefix_candidates(word) {
+            if candidate.chars().count() < 2 {
+                continue;
+            }
+            if self.roots.contains(&candidate) {
+                return Some(candidate);
+            }
+            if let Some(root) = self.unprefix(&candidate, depth - 1) {
+                return Some(root);
+            }
+        }
+        None
+    }
+}
+
+/// Every way of removing one prefix from `word`, undoing the nasal assimilation of meN- and peN-:
+/// `memukul` may come from `pukul`, `menulis` from `tulis`, `menyapu` from `sapu`,
+/// `mengira` from `kira` and `mengecat` from `cat`.
+pub(crate) fn prefix_candidates(word: &str) -> Vec<String> {
+    let mut candidates = Vec::new();
+    for nasal in ["me", "pe"] {
+        let Some(rest) = word.strip_prefix(nasal) else {
+            continue;
+        };
+        if let Some(rest) = rest.strip_prefix("nge") {
+            candidates.push(rest.to_string());
+        }
+        if let Some(rest) = rest.strip_prefix("ng") {
+            candidates.push(rest.to_string());
+            candidates.push(format!("k{}", rest));
+        } else if let Some(rest) = rest.strip_prefix("ny") {
+            candidates.push(format!("s{}", rest));
+        } else if let Some(rest) = rest.strip_prefix('n') {
+            candidates.push(rest.to_string());
+            candidates.push(format!("t{}", rest));
+        } else if let Some(rest) = rest.strip_prefix('m') {
+            candidates.push(rest.to_string());
+            candidates.push(format!("p{}", rest));
+        }
+        // me- and pe- before l, r, w, y and the like.
+        candidates.push(rest.to_string());
+    }
+    for prefix in [
+        "ber", "bel", "be", "ter", "te", "per", "pel", "di", "ke", "se",
+    ] {
+        if let Some(rest) = word.strip_prefix(prefix) {
+            candidates.push(rest.to_string());
+        }
+    }
+    candidates
+}
+
+#[cfg(test)]
+#[test]
+fn test_lemma() {
+    let stemmer = Stemmer::load(None).unwrap();
+    for (word, root) in [
+        ("pembinaan", "bina"),
+        ("membina", "bina"),
+        ("Dibina", "bina"),
+        ("menulis", "tulis"),
+        ("memukul", "pukul"),
+        ("menyapu", "sapu"),
+        ("mengira", "kira"),
+        ("mengambil", "ambil"),
+        ("mengecat", "cat"),
+        ("memberikan", "beri"),
+        ("memperbaiki", "baik"),
+        ("keadaan", "ada"),
+        ("belajar", "ajar"),
+        ("pelajaran", "ajar"),
+        ("saluang", "saluang"),
+        ("makanan", "makan"),
+        ("berita", "berita"),
+        ("memberitakan", "berita"),
+        ("pembelian", "beli"),
+    ] {
+        assert_eq!(stemmer.lemma(word), root, "{}", word);
+    }
+    // Roots that only look affixed are not stemmed any further.
+    for word in [
+        "makan", "ikan", "perang", "tentang", "kemudian", "negeri", "selasa", "bekas", "teman",
+        "meja", "pelangi", "periuk", "kenari", "mereka", "tetapi", "kerana", "memang", "bahan",
+        "perahu", "dian", "sekolah", "langkah", "belakang", "segala", "bersih", "permai", "pemuda",
+    ] {
+        assert_eq!(stemmer.lemma(word), word, "{}", word);
+    }
+}
@@ -0,0 +1,76 @@
+use std::collections::HashSet;
+
+use crate::{cli, parser::Token};
+
+const MALAY: &str = include_str!("../dictionaries/stopwords-ms.txt");
+const INDONESIAN: &str = include_str!("../dictionaries/stopwords-id.txt");
+const ENGLISH: &str = include_str!("../dictionaries/stopwords-en.txt");
+
+/// Parses a word list: one word per line, blank lines and `#` comments ignored, matched in lowercase.
+pub(crate) fn parse(text: &str) -> HashSet<String> {
+    text.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Reads a stopword list in the format of [`parse`].
+pub(crate) fn load(path: &std::path::Path) -> std::io::Result<HashSet<String>> {
+    Ok(parse(&std::fs::read_to_string(path)?))
+}
+
+/// The bundled list of `language`.
+pub(crate) fn builtin(language: cli::StopwordLanguage) -> HashSet<String> {
+    parse(match language {
+        cli::StopwordLanguage::Ms => MALAY,
+        cli::StopwordLanguage::Id => INDONESIAN,
+        cli::StopwordLanguage::En => ENGLISH,
+    })
+}
+
+/// Union of the bundled lists and the files given on the command line.
+pub(crate) fn from_args(arg: &cli::Stopwords) -> std::io::Result<HashSet<String>> {
+    let mut stopwords = HashSet::new();
+    if !arg.no_builtin_stopwords {
+        for language in &arg.stopword_languages {
+            stopwords.extend(builtin(*language));
+        }
+    }
+    for path in &arg.stopwords {
+        stopwords.extend(load(path)?);
+    }
+    Ok(stopwords)
+}
+
+/// Turns every word whose key (see [`crate::ingest::ScanOptions::lower`]) is a stopword into a `Token::Stop`.
+pub(crate) fn mark(
+    tokens: Vec<Token>,
+    stopwords: &HashSet<String>,
+    key: impl Fn(&str) -> String,
+) -> Vec<Token> {
+    tokens
+        .into_iter()
+        .map(|token| match token {
+            Token::Text(word) if stopwords.contains(&key(&word)) => Token::Stop(word),
+            token => token,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+#[test]
+fn test_mark() {
+    let stopwords = builtin(cli::StopwordLanguage::Ms);
+    let tokens = ["Alat", "yang", "dibuat", "DARI", "buluh"]
+        .map(|w| Token::from(w.to_string()))
+        .to_vec();
+    let marked = mark(tokens, &stopwords, str::to_lowercase);
+    let stops = marked
+        .iter()
+        .filter(|t| matches!(t, Token::Stop(_)))
+        .map(Token::unwrap)
+        .collect::<Vec<_>>();
+    assert_eq!(stops, vec!["yang", "DARI"]);
+    assert_eq!(crate::parser::ngrams(&marked, 2).len(), 0);
+}